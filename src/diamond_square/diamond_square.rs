@@ -1,8 +1,10 @@
 use std::usize;
 
 use rand::{rngs::ThreadRng, Rng};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DsquareOpts {
     pub size: u32,
     pub roughness: f64,
@@ -4,52 +4,139 @@ extern crate noise;
 
 use noise::{Seedable, Perlin, NoiseFn};
 use rand::Rng; 
-use rand::seq::SliceRandom; 
+use serde::{Deserialize, Serialize};
+use super::settlements::{build_roads, place_settlements, Road, RoadOpts, Settlement, SettlementOpts};
+use super::climate::{temperature_map, ClimateOpts};
 
 
-const DEEP_WATER_THRESHOLD: f64 = 0.3;
+pub const DEEP_WATER_THRESHOLD: f64 = 0.3;
 const DEEP_THRESHOLD: f64 = 0.2;
 const MOUNTAIN_THRESHOLD: f64 = 0.7;
 pub const DARK_FOREST_THRESHOLD: f64 = 0.45;
 const SNOW_THRESHOLD: f64 = 0.82; 
 const HUMIDITY_THRESHOLD: f64 = 0.7;
 const GLACEIL_THRESHOLD: f64 = 0.25; 
-const DESSERT_THRESHOLD: f64 = 0.75;
 
 
 const FOREST_AP: f64 = 0.3;
 const DARK_FOREST_AP: f64 = 0.6;
 
-const DEEP_WATER: f64 = -2.0;
-const WATER: f64 = -1.0;
-const DESSERT: f64 = 2.0;
-const MOUTAIN: f64 = 3.0;
-const MOUNTAIN_SNOW: f64 = 4.0;
-const GLACIAL: f64 = 5.0;
-const DESSERT_SHARA: f64 = 6.0;
-const DARK_FOREST: f64 = 7.0;
-const MARSH: f64 = 8.0;
-const CITY: f64 = 10.0;
-const VILLAGE: f64 = 11.0;
-const ROUTE: f64 = 12.0;
-
-
-const VILLAGE_VALUE: f64 = 10.0; 
-const VILLE_VALUE: f64 = 11.0; 
-const PIXELS_PAR_VILLAGE: usize = 8000;
-const PIXELS_PAR_VILLE: usize = 16000;
-
-const VILLAGE_DISTANCE_MAX_CARREE: usize = 100 * 100; 
-const VILLE_DISTANCE_MAX_CARREE: usize = 200 * 200;  
-const VILLAGE_MAX_ROUTES: usize = 3;
-const CITY_MAX_ROUTES: usize = 5;
+pub const DEEP_WATER: f64 = -2.0;
+pub const WATER: f64 = -1.0;
+pub const DESSERT: f64 = 2.0;
+pub const MOUTAIN: f64 = 3.0;
+pub const MOUNTAIN_SNOW: f64 = 4.0;
+pub const GLACIAL: f64 = 5.0;
+pub const DESSERT_SHARA: f64 = 6.0;
+pub const DARK_FOREST: f64 = 7.0;
+pub const MARSH: f64 = 8.0;
+pub const CITY: f64 = 10.0;
+pub const VILLAGE: f64 = 11.0;
+pub const ROUTE: f64 = 12.0;
+pub const TUNDRA: f64 = 13.0;
+pub const TAIGA: f64 = 14.0;
+pub const TEMPERATE_FOREST: f64 = 15.0;
+pub const GRASSLAND: f64 = 16.0;
+pub const SHRUBLAND: f64 = 17.0;
+pub const SAVANNA: f64 = 18.0;
+pub const TROPICAL_FOREST: f64 = 19.0;
+pub const RAINFOREST: f64 = 20.0;
+
+
+// Whittaker diagram: rows are temperature classes (polar -> tropical),
+// columns are humidity classes (arid -> wet), one table per altitude band.
+const WHITTAKER_TEMPERATURES: [f64; 4] = [0.05, 0.15, GLACEIL_THRESHOLD, 0.45];
+const WHITTAKER_HUMIDITIES: [f64; 3] = [0.3, 0.5, HUMIDITY_THRESHOLD];
+
+const WHITTAKER_LOWLAND: [[f64; 4]; 5] = [
+    [GLACIAL, GLACIAL, GLACIAL, GLACIAL],
+    [TUNDRA, TUNDRA, TUNDRA, TUNDRA],
+    [GRASSLAND, TAIGA, TAIGA, TAIGA],
+    [SHRUBLAND, GRASSLAND, TEMPERATE_FOREST, DARK_FOREST],
+    [DESSERT_SHARA, SAVANNA, TROPICAL_FOREST, RAINFOREST],
+];
+
+const WHITTAKER_MONTANE: [[f64; 4]; 5] = [
+    [GLACIAL, GLACIAL, GLACIAL, GLACIAL],
+    [TUNDRA, TUNDRA, TUNDRA, TUNDRA],
+    [TUNDRA, TAIGA, TAIGA, TAIGA],
+    [SHRUBLAND, GRASSLAND, TAIGA, TEMPERATE_FOREST],
+    [SHRUBLAND, SAVANNA, TEMPERATE_FOREST, DARK_FOREST],
+];
+
+const WHITTAKER_ALPINE: [[f64; 4]; 5] = [
+    [MOUNTAIN_SNOW, MOUNTAIN_SNOW, MOUNTAIN_SNOW, MOUNTAIN_SNOW],
+    [MOUTAIN, MOUNTAIN_SNOW, MOUNTAIN_SNOW, MOUNTAIN_SNOW],
+    [MOUTAIN, MOUTAIN, TUNDRA, MOUNTAIN_SNOW],
+    [MOUTAIN, MOUTAIN, TUNDRA, TUNDRA],
+    [MOUTAIN, MOUTAIN, GRASSLAND, GRASSLAND],
+];
+
+const MONTANE_THRESHOLD: f64 = 0.55;
+// biomes set before the Whittaker pass: water, beaches and marshes
+const PRE_CLASSIFIED: [f64; 4] = [DEEP_WATER, WATER, DESSERT, MARSH];
+
 
 const MIN_DESERT_SIZE: usize = 40;
 const MIN_GLACIAL_SIZE: usize = 40;
 
 
+// thresholds of the biome stage and its optional passes, the defaults are
+// the constants above
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiomeOpts
+{
+    // altitudes below are deep water, and shallow water below `deep`
+    pub deep_water: f64,
+    pub deep: f64,
+    pub montane: f64,
+    pub mountain: f64,
+    pub snow: f64,
+    // temperature above which the land is temperate, kept between the cold
+    // and the warm classes of the Whittaker diagram
+    pub temperate: f64,
+    // humidity above which the land is wet, kept above the humid class
+    pub wet: f64,
+    // humidity above which the coasts turn into marshes
+    pub marsh_humidity: f64,
+    pub marsh: bool,
+    pub desert_cleanup: bool,
+    // settlements and roads with their default options, the Settlements and
+    // Roads nodes place them with more control
+    pub settlements: bool,
+    pub roads: bool,
+}
+
+impl Default for BiomeOpts
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            deep_water: DEEP_WATER_THRESHOLD,
+            deep: DEEP_THRESHOLD,
+            montane: MONTANE_THRESHOLD,
+            mountain: MOUNTAIN_THRESHOLD,
+            snow: SNOW_THRESHOLD,
+            temperate: GLACEIL_THRESHOLD,
+            wet: HUMIDITY_THRESHOLD,
+            marsh_humidity: MOUNTAIN_THRESHOLD,
+            marsh: true,
+            desert_cleanup: true,
+            settlements: false,
+            roads: false,
+        }
+    }
+}
+
+
 pub fn generate_perlin_noise_matrix(width: usize, height: usize, scale: f64) -> Vec<Vec<f64>> {
-    let seed = rand::random::<u32>();
+    generate_seeded_perlin_noise_matrix(width, height, scale, rand::random::<u32>())
+}
+
+// same noise for the same seed
+pub fn generate_seeded_perlin_noise_matrix(width: usize, height: usize, scale: f64, seed: u32) -> Vec<Vec<f64>> {
     let perlin = Perlin::new().set_seed(seed);
 
     let mut matrix = vec![vec![0.0; width]; height];
@@ -276,6 +363,24 @@ fn mark_water_zones
     deep_water_zones
 }
 
+// cells of the water biomes, the way `apply_biomes_with` classifies them
+pub fn water_mask
+(
+    altitude_matrix: &Vec<Vec<f64>>,
+    opts: &BiomeOpts
+) -> Vec<Vec<bool>> 
+{
+    let size = altitude_matrix.len();
+    let mut base_matrix = vec![vec![0.0; size]; size];
+
+    apply_deep_water(&mut base_matrix, altitude_matrix, opts.deep_water);
+    apply_water(&mut base_matrix, altitude_matrix, opts.deep);
+
+    base_matrix.iter()
+        .map(|row| row.iter().map(|&x| x == WATER || x == DEEP_WATER).collect())
+        .collect()
+}
+
 fn enlarge_deep_water_zones
 (
     deep_water_zones: &mut Vec<Vec<bool>>, 
@@ -419,12 +524,53 @@ fn extend_zone_to_shallow_water
 
 
 
-fn apply_mountain_biome
+fn whittaker_class
+(
+    value: f64,
+    thresholds: &[f64]
+) -> usize
+{
+    thresholds.iter().take_while(|&&threshold| value >= threshold).count()
+}
+
+fn classify_whittaker
+(
+    altitude: f64,
+    temperature: f64,
+    humidity: f64,
+    opts: &BiomeOpts
+) -> f64
+{
+    let table = if altitude > opts.mountain 
+    {
+        &WHITTAKER_ALPINE
+    } 
+    else if altitude > opts.montane 
+    {
+        &WHITTAKER_MONTANE
+    } 
+    else 
+    {
+        &WHITTAKER_LOWLAND
+    };
+
+    let mut temperatures = WHITTAKER_TEMPERATURES;
+    temperatures[2] = opts.temperate.clamp(temperatures[1], temperatures[3]);
+    let mut humidities = WHITTAKER_HUMIDITIES;
+    humidities[2] = opts.wet.max(humidities[1]);
+
+    let t = whittaker_class(temperature, &temperatures);
+    let h = whittaker_class(humidity, &humidities);
+    table[t][h]
+}
+
+fn apply_whittaker_biomes
 (
     base_matrix: &mut Vec<Vec<f64>>, 
     altitude_matrix: &Vec<Vec<f64>>, 
-    mountain_threshold: f64, 
-    snow_threshold: f64
+    temperature_matrix: &Vec<Vec<f64>>, 
+    humidity_matrix: &Vec<Vec<f64>>,
+    opts: &BiomeOpts
 ) 
 {
     let size = base_matrix.len();
@@ -432,19 +578,27 @@ fn apply_mountain_biome
     {
         for j in 0..size 
         {
-            if altitude_matrix[i][j] > mountain_threshold 
+            if PRE_CLASSIFIED.contains(&base_matrix[i][j]) 
             {
-                if altitude_matrix[i][j] > snow_threshold 
-                {
-                    base_matrix[i][j] = MOUNTAIN_SNOW; 
-                } 
-                else 
-                {
-                    base_matrix[i][j] = MOUTAIN; 
-                }
+                continue;
+            }
+
+            let altitude = altitude_matrix[i][j];
+            if altitude > opts.snow 
+            {
+                base_matrix[i][j] = MOUNTAIN_SNOW;
+                continue;
             }
+
+            base_matrix[i][j] = classify_whittaker(altitude, temperature_matrix[i][j], humidity_matrix[i][j], opts);
         }
     }
+
+    if opts.desert_cleanup 
+    {
+        delete_small_deserts(base_matrix, DESSERT_SHARA, SAVANNA, MIN_DESERT_SIZE);
+    }
+    delete_small_glacial(base_matrix, GLACIAL, TUNDRA, MIN_GLACIAL_SIZE);
 }
 
 
@@ -489,30 +643,6 @@ fn apply_dark_forest
 
 
 
-fn apply_desert_biome
-(
-    base_matrix: &mut Vec<Vec<f64>>,
-    temperature_matrix: &Vec<Vec<f64>>,
-    temperature_threshold: f64,
-) 
-{
-    let size = base_matrix.len();
-    for i in 0..size 
-    {
-        for j in 0..size 
-        {
-            if temperature_matrix[i][j] > temperature_threshold && 
-            base_matrix[i][j] != DEEP_WATER && base_matrix[i][j] != WATER &&
-            base_matrix[i][j] != MOUNTAIN_SNOW && /*base_matrix[i][j] != GLACIAL && */
-            base_matrix[i][j] != MOUTAIN //&& base_matrix[i][j] != MARSH
-            {
-                base_matrix[i][j] = DESSERT_SHARA; 
-            }
-        }
-    }
-    delete_small_deserts(base_matrix,DESSERT_SHARA,FOREST_AP,MIN_DESERT_SIZE);
-}
-
 fn delete_small_deserts
 (
     base_matrix: &mut Vec<Vec<f64>>,
@@ -562,31 +692,6 @@ fn delete_small_deserts
 }
 
 
-fn apply_glacial_biome
-(
-    base_matrix: &mut Vec<Vec<f64>>,
-    temperature_matrix: &Vec<Vec<f64>>,
-    temperature_threshold: f64,
-) 
-{
-    let size = base_matrix.len();
-    for i in 0..size 
-    {
-        for j in 0..size 
-        {
-            if temperature_matrix[i][j] < temperature_threshold && 
-            base_matrix[i][j] != DEEP_WATER && base_matrix[i][j] != WATER &&
-            base_matrix[i][j] != MOUNTAIN_SNOW && 
-            base_matrix[i][j] != MOUTAIN && base_matrix[i][j] != MARSH
-            {
-                base_matrix[i][j] = GLACIAL; 
-            }
-        }
-    }
-    delete_small_glacial(base_matrix,GLACIAL,FOREST_AP,MIN_GLACIAL_SIZE);
-
-}
-
 fn delete_small_glacial
 (
     base_matrix: &mut Vec<Vec<f64>>,
@@ -707,441 +812,10 @@ fn apply_marsh_biome
 
 
 
-fn identifier_et_mesurer_les_plaines
-(
-    base_matrix: &Vec<Vec<f64>>
-) -> Vec<(usize, usize, usize)> 
-{
-    let size = base_matrix.len();
-    let mut visited = vec![vec![false; size]; size];
-    let mut zones_de_plaines = Vec::new();
-
-    for i in 0..size 
-    {
-        for j in 0..size 
-        {
-            if base_matrix[i][j] < DARK_FOREST_THRESHOLD && base_matrix[i][j] > 0.0 && !visited[i][j] 
-            {
-                let taille = mesurer_zone_de_plaine(i, j, base_matrix, &mut visited, size);
-                zones_de_plaines.push((i, j, taille));
-            }
-        }
-    }
-
-    zones_de_plaines
-}
-
-fn mesurer_zone_de_plaine(
-    i: usize, 
-    j: usize, 
-    base_matrix: &Vec<Vec<f64>>, 
-    visited: &mut Vec<Vec<bool>>, 
-    size: usize
-) -> usize 
-{
-    let mut stack = Vec::new();
-    stack.push((i, j));
-
-    let mut area_size = 0;
-
-    let directions = [(0, 1), (1, 0), (0, -1), (-1, 0)];
-
-    while let Some((current_i, current_j)) = stack.pop() 
-    {
-        if current_i >= size || current_j >= size || visited[current_i][current_j] || (base_matrix[current_i][current_j] > DARK_FOREST_THRESHOLD && base_matrix[current_i][current_j] > 0.0) 
-        {
-            continue;
-        }
-
-        visited[current_i][current_j] = true;
-        area_size += 1;
-
-        for (di, dj) in directions.iter() 
-        {
-            let new_i = current_i.wrapping_add(*di as usize);
-            let new_j = current_j.wrapping_add(*dj as usize);
-
-            if new_i < size && new_j < size && !visited[new_i][new_j] && base_matrix[new_i][new_j] < DARK_FOREST_THRESHOLD && base_matrix[new_i][new_j] > 0.0 
-            {
-                stack.push((new_i, new_j));
-            }
-        }
-    }
-
-    area_size
-}
-
-
-use rand::thread_rng;
-
-fn generer_villages_et_villes
-(
-    base_matrix: &mut Vec<Vec<f64>>, 
-    zones_de_plaines: Vec<(usize, usize, usize)>
-) 
-{
-    let mut rng = thread_rng();
-
-    for (_, _, taille) in zones_de_plaines.iter() 
-    {
-        let mut village_positions = Vec::new();
-        let mut city_positions = Vec::new();
-        
-        let mut compteur_pixels = 0;
-        let mut villages_a_placer = 0;
-
-        for i in 0..base_matrix.len() 
-        {
-            for j in 0..base_matrix[0].len() 
-            {
-                if base_matrix[i][j] < DARK_FOREST_THRESHOLD && base_matrix[i][j] > 0.0 
-                {
-                    village_positions.push((i, j));
-                    compteur_pixels += 1;
-                    if compteur_pixels % PIXELS_PAR_VILLE == 0 
-                    {
-                        city_positions.push((i, j));
-                        villages_a_placer = 0; 
-                    } 
-                    else if compteur_pixels % PIXELS_PAR_VILLAGE == 0 
-                    {
-                        villages_a_placer += 1;
-                        if villages_a_placer < 5 
-                        {
-                            village_positions.push((i, j));
-                        }
-                    }
-                }
-            }
-        }
-
-        let villes_a_placer = taille / PIXELS_PAR_VILLE;
-        for _ in 0..villes_a_placer 
-        {
-            if let Some(&(i, j)) = city_positions.choose(&mut rng) 
-            {
-                placer_construction(base_matrix, i, j, VILLE_VALUE);
-            }
-        }
-
-        let villages_a_placer = taille / PIXELS_PAR_VILLAGE;
-        for _ in 0..villages_a_placer 
-        {
-            if let Some(&(i, j)) = village_positions.choose(&mut rng) 
-            {
-                placer_construction(base_matrix, i, j, VILLAGE_VALUE);
-            }
-        }
-    }
-}
-
-fn placer_construction
-(
-    base_matrix: &mut Vec<Vec<f64>>, 
-    i: usize, 
-    j: usize, 
-    valeur: f64
-) 
-{
-    match valeur 
-    {
-        11.0 => 
-        {
-            if i > 2 && j > 2 && (i + 2) < base_matrix.len() && (j + 2) < base_matrix[0].len() 
-            {
-                base_matrix[i - 1][j] = valeur;
-                base_matrix[i + 1][j] = valeur;
-                base_matrix[i][j - 1] = valeur;
-                base_matrix[i][j + 1] = valeur;
-                base_matrix[i - 2][j] = valeur;
-                base_matrix[i + 2][j] = valeur;
-                base_matrix[i][j - 2] = valeur;
-                base_matrix[i][j + 2] = valeur;
-                base_matrix[i][j] = valeur;
-            }
-        },
-        10.0 => 
-        {
-            if i > 2 && j > 2  && (i + 3) < base_matrix.len() && (j + 3) < base_matrix[0].len() 
-            {
-                base_matrix[i - 1][j] = valeur;
-                base_matrix[i + 1][j] = valeur;
-                base_matrix[i][j - 1] = valeur;
-                base_matrix[i][j + 1] = valeur;
-                base_matrix[i - 2][j] = valeur;
-                base_matrix[i + 2][j] = valeur;
-                base_matrix[i][j - 2] = valeur;
-                base_matrix[i][j + 2] = valeur;
-                base_matrix[i][j] = valeur;
-            }
-        },
-        _ => {}
-    }
-}
-
-
-
 use std::collections::HashSet;
-use std::cmp::Ordering;
-
-struct UnionFind 
-{
-    parent: Vec<usize>,
-    rank: Vec<usize>,
-}
-
-impl UnionFind 
-{
-    fn new(n: usize) -> Self 
-    {
-        UnionFind 
-        {
-            parent: (0..n).collect(),
-            rank: vec![0; n],
-        }
-    }
-
-    fn find(&mut self, x: usize) -> usize 
-    {
-        if self.parent[x] != x 
-        {
-            self.parent[x] = self.find(self.parent[x]);
-        }
-        self.parent[x]
-    }
-
-    fn union(&mut self, x: usize, y: usize) 
-    {
-        let root_x = self.find(x);
-        let root_y = self.find(y);
-
-        if root_x != root_y 
-        {
-            if self.rank[root_x] > self.rank[root_y] 
-            {
-                self.parent[root_y] = root_x;
-            } 
-            else if self.rank[root_x] < self.rank[root_y] 
-            {
-                self.parent[root_x] = root_y;
-            } 
-            else 
-            {
-                self.parent[root_y] = root_x;
-                self.rank[root_x] += 1;
-            }
-        }
-    }
-}
-
-#[derive(Copy, Clone, Eq, PartialEq)]
-struct Edge 
-{
-    cost: usize,
-    start: usize,
-    end: usize,
-}
-
-impl Ord for Edge 
-{
-    fn cmp(&self, other: &Self) -> Ordering 
-    {
-        other.cost.cmp(&self.cost).reverse()
-    }
-}
-
-impl PartialOrd for Edge 
-{
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> 
-    {
-        Some(self.cmp(other))
-    }
-}
-
-fn kruskal
-(
-    points: Vec<(usize, usize)>, 
-    base_matrix: &mut Vec<Vec<f64>>, 
-    route_value: f64, 
-    restricted_values: &HashSet<i64>
-) 
-{
-    let mut edges = Vec::new();
-    let n = points.len();
-
-    for i in 0..n 
-    {
-        for j in i + 1..n 
-        {
-            let start = points[i];
-            let end = points[j];
-            let distance = distance_carree(start, end);
-            let max_distance = if base_matrix[start.0][start.1] == VILLAGE || base_matrix[end.0][end.1] == VILLAGE 
-            {
-                VILLAGE_DISTANCE_MAX_CARREE
-            } 
-            else 
-            {
-                VILLE_DISTANCE_MAX_CARREE
-            };
-            if distance <= max_distance 
-            {
-                edges.push(Edge { cost: distance, start: i, end: j });
-            }
-        }
-    }
-
-    edges.sort_by(|a, b| a.cost.cmp(&b.cost));
-
-    let mut uf = UnionFind::new(n);
-    let mut mst = Vec::new();
-
-    let mut connections = vec![0; n];
-
-    for edge in edges 
-    {
-        if uf.find(edge.start) != uf.find(edge.end) 
-        {
-            let start_value = base_matrix[points[edge.start].0][points[edge.start].1];
-            let end_value = base_matrix[points[edge.end].0][points[edge.end].1];
-            let max_connections = if start_value == VILLAGE || end_value == VILLAGE 
-            {
-                VILLAGE_MAX_ROUTES
-            } 
-            else 
-            {
-                CITY_MAX_ROUTES
-            };
-
-            if connections[edge.start] < max_connections && connections[edge.end] < max_connections 
-            {
-                if tracer_route(base_matrix, points[edge.start], points[edge.end], route_value, restricted_values) 
-                {
-                    uf.union(edge.start, edge.end);
-                    mst.push(edge);
-                    connections[edge.start] += 1;
-                    connections[edge.end] += 1;
-                }
-            }
-        }
-    }
-}
-
-fn bezier_curve
-(
-    start: (usize, usize), 
-    control: (usize, usize), 
-    end: (usize, usize)
-) -> Vec<(usize, usize)> 
-{
-    let mut path = Vec::new();
-    for t in 0..=100 
-    {
-        let t = t as f64 / 100.0;
-        let x = ((1.0 - t) * (1.0 - t) * start.0 as f64 + 2.0 * (1.0 - t) * t * control.0 as f64 + t * t * end.0 as f64) as usize;
-        let y = ((1.0 - t) * (1.0 - t) * start.1 as f64 + 2.0 * (1.0 - t) * t * control.1 as f64 + t * t * end.1 as f64) as usize;
-        path.push((x, y));
-    }
-    path
-}
-
-fn tracer_route
-(
-    base_matrix: &mut Vec<Vec<f64>>,
-    start: (usize, usize),
-    end: (usize, usize),
-    route_value: f64,
-    restricted_values: &HashSet<i64>,
-) -> bool 
-{
-    let control = ((start.0 + end.0) / 2, (start.1 + end.1) / 2); 
-    let path = bezier_curve(start, control, end);
 
-    for &(px, py) in &path 
-    {
-        if restricted_values.contains(&f64_to_i64(base_matrix[px][py])) 
-        {
-            return false;
-        }
-    }
 
-    for &(px, py) in &path 
-    {
-        if base_matrix[px][py] != route_value 
-        {
-            base_matrix[px][py] = route_value;
-        }
-    }
-
-    true
-}
-
-fn f64_to_i64(value: f64) -> i64 
-{
-    value.to_bits() as i64
-}
-
-fn distance_carree((x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> usize 
-{
-    let dx = (x2 as isize - x1 as isize).abs() as usize;
-    let dy = (y2 as isize - y1 as isize).abs() as usize;
-    dx * dx + dy * dy
-}
-
-fn collecter_villes_et_villages
-(
-    base_matrix: &Vec<Vec<f64>>,
-    ville_value: f64,
-    village_value: f64
-) -> Vec<(usize, usize)> 
-{
-    let mut points = Vec::new();
-    let size = base_matrix.len();
-
-    for i in 2..(size - 2) 
-    {
-        for j in 2..(size - 2) 
-        {
-            if base_matrix[i][j] == ville_value || base_matrix[i][j] == village_value 
-            {
-                if base_matrix[i - 1][j] == base_matrix[i][j] &&
-                   base_matrix[i + 1][j] == base_matrix[i][j] &&
-                   base_matrix[i][j - 1] == base_matrix[i][j] &&
-                   base_matrix[i][j + 1] == base_matrix[i][j] &&
-                   base_matrix[i - 2][j] == base_matrix[i][j] &&
-                   base_matrix[i + 2][j] == base_matrix[i][j] &&
-                   base_matrix[i][j - 2] == base_matrix[i][j] &&
-                   base_matrix[i][j + 2] == base_matrix[i][j] 
-                {
-                    points.push((i, j));
-                }
-            }
-        }
-    }
-
-    // println!("Nombre de points de villes et villages collectés : {}", points.len());
-    points
-}
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-fn apply_convolution(matrix: &mut Vec<Vec<f64>>) 
+pub fn apply_convolution(matrix: &mut Vec<Vec<f64>>) 
 {
     let size = matrix.len();
     let mut new_matrix = matrix.clone();
@@ -1247,9 +921,32 @@ pub fn apply_biomes
     humidity_matrix: &Vec<Vec<f64>>
 ) 
 {
+    // the temperature given is only noise, it is cooled with the altitude
+    // and the latitude the way the Coloration node does
+    let temperature_matrix = temperature_map(altitude_matrix, temperature_matrix, &ClimateOpts::default());
 
-    apply_deep_water(base_matrix, altitude_matrix, DEEP_WATER_THRESHOLD);
-    apply_water(base_matrix, altitude_matrix, DEEP_THRESHOLD);
+    let opts = BiomeOpts
+    {
+        settlements: true,
+        roads: true,
+        ..BiomeOpts::default()
+    };
+
+    apply_biomes_with(base_matrix, altitude_matrix, &temperature_matrix, humidity_matrix, &opts);
+}
+
+pub fn apply_biomes_with
+(
+    base_matrix: &mut Vec<Vec<f64>>, 
+    altitude_matrix: &Vec<Vec<f64>>,
+    temperature_matrix: &Vec<Vec<f64>>,
+    humidity_matrix: &Vec<Vec<f64>>,
+    opts: &BiomeOpts
+) -> (Vec<Settlement>, Vec<Road>)
+{
+
+    apply_deep_water(base_matrix, altitude_matrix, opts.deep_water);
+    apply_water(base_matrix, altitude_matrix, opts.deep);
     apply_sand_biome(base_matrix, altitude_matrix, DESSERT as usize);
 
     let size = base_matrix.len(); 
@@ -1257,33 +954,63 @@ pub fn apply_biomes
     apply_sand_to_shallow_water_transformation(base_matrix,&water_zones,base_matrix.len(),DEEP_WATER,DESSERT);
     
 
-    apply_mountain_biome(base_matrix, altitude_matrix, MOUNTAIN_THRESHOLD,SNOW_THRESHOLD);
     //apply_dark_forest(base_matrix, altitude_matrix, humidity_matrix, MOUTAIN, DARK_FOREST_THRESHOLD);
-    apply_marsh_biome(base_matrix,humidity_matrix,temperature_matrix,MOUNTAIN_THRESHOLD,MARSH,DESSERT,WATER,DEEP_WATER);
-    apply_desert_biome(base_matrix, temperature_matrix, DESSERT_THRESHOLD);
-    apply_glacial_biome(base_matrix, temperature_matrix, GLACEIL_THRESHOLD);
-
-    let zones_de_plaines = identifier_et_mesurer_les_plaines(base_matrix);
-    generer_villages_et_villes(base_matrix, zones_de_plaines);
-
-
-    //let points = collecter_villes_et_villages(base_matrix, CITY, VILLAGE);
-    //generer_routes_simples(base_matrix, points, ROUTE, CITY, VILLAGE);
+    if opts.marsh 
+    {
+        apply_marsh_biome(base_matrix,humidity_matrix,temperature_matrix,opts.marsh_humidity,MARSH,DESSERT,WATER,DEEP_WATER);
+    }
+    apply_whittaker_biomes(base_matrix, altitude_matrix, temperature_matrix, humidity_matrix, opts);
 
-    let points = collecter_villes_et_villages(base_matrix, CITY, VILLAGE);
-    let restricted_values = HashSet::from([
-        f64_to_i64(DEEP_WATER),
-        f64_to_i64(WATER),
-        f64_to_i64(DESSERT),
-        f64_to_i64(MOUTAIN),
-        f64_to_i64(MOUNTAIN_SNOW),
-        f64_to_i64(GLACIAL),
-        f64_to_i64(DESSERT_SHARA),
-        f64_to_i64(MARSH),
-    ]);
-    kruskal(points, base_matrix, ROUTE, &restricted_values);
+    let settlements = if opts.settlements 
+    {
+        place_settlements(base_matrix, &SettlementOpts::default())
+    } 
+    else 
+    {
+        Vec::new()
+    };
+    // the roads only join the settlements placed above
+    let roads = if opts.roads 
+    {
+        build_roads(base_matrix, &settlements, &RoadOpts::default())
+    } 
+    else 
+    {
+        Vec::new()
+    };
 
+    (settlements, roads)
+}
 
+pub fn biome_color(val: f64) -> Color 
+{
+    match val 
+    {
+        -2.0 => Color::BLUE,
+        -1.0 => Color::DARKBLUE,
+        2.0 => Color::GOLD,
+        3.0 => Color::GRAY,
+        4.0 => Color::LIGHTGRAY, 
+        5.0 => Color::WHITE, 
+        6.0 => Color::YELLOW,
+        7.0 => Color::DARKGREEN,
+        8.0 => Color::PURPLE,
+        -5.0 => Color::BLACK,
+        10.0 => Color::RED,
+        11.0 => Color::ORANGE,
+        12.0 => Color::BLACK,
+        13.0 => rcolor(190, 190, 160, 255),
+        14.0 => rcolor(50, 100, 70, 255),
+        15.0 => rcolor(60, 140, 60, 255),
+        16.0 => Color::GREEN,
+        17.0 => rcolor(170, 170, 100, 255),
+        18.0 => rcolor(200, 190, 90, 255),
+        19.0 => rcolor(70, 160, 40, 255),
+        20.0 => rcolor(0, 90, 30, 255),
+        _ if val >= 0.0 && val < DARK_FOREST_THRESHOLD => Color::GREEN,
+        _ if val >= DARK_FOREST_THRESHOLD => Color::DARKGREEN,
+        _ => Color::DARKGREEN,
+    }
 }
 
 
@@ -1317,25 +1044,7 @@ pub fn test_2D(img_texture: &mut Image, img_water: &mut Image) -> Vec<Vec<f64>>
     {
         for (j, &val) in row.iter().enumerate() 
         {
-            let color = match val 
-            {
-                -2.0 => Color::BLUE,
-                -1.0 => Color::DARKBLUE,
-                2.0 => Color::GOLD,
-                3.0 => Color::GRAY,
-                4.0 => Color::LIGHTGRAY, 
-                5.0 => Color::WHITE, 
-                6.0 => Color::YELLOW,
-                7.0 => Color::DARKGREEN,
-                8.0 => Color::PURPLE,
-                -5.0 => Color::BLACK,
-                10.0 => Color::RED,
-                11.0 => Color::ORANGE,
-                12.0 => Color::BLACK,
-                _ if val >= 0.0 && val < DARK_FOREST_THRESHOLD => Color::GREEN,
-                _ if val >= DARK_FOREST_THRESHOLD => Color::DARKGREEN,
-                _ => Color::DARKGREEN,
-            };
+            let color = biome_color(val);
             image.draw_pixel(j as i32, i as i32, color);
         }
     }
@@ -1352,24 +1061,7 @@ pub fn test_2D(img_texture: &mut Image, img_water: &mut Image) -> Vec<Vec<f64>>
         {
             for (j, &val) in row.iter().enumerate() 
             {
-                let color = match val {
-                    -2.0 => Color::BLUE, 
-                    -1.0 => Color::DARKBLUE,
-                    2.0 => Color::GOLD,
-                    3.0 => Color::GRAY, 
-                    4.0 => Color::LIGHTGRAY, 
-                    5.0 => Color::WHITE, 
-                    6.0 => Color::YELLOW,
-                    7.0 => Color::DARKGREEN,
-                    8.0 => Color::PURPLE,
-                    -5.0 => Color::BLACK,
-                    10.0 => Color::RED,
-                    11.0 => Color::ORANGE,
-                    12.0 => Color::BLACK,
-                    _ if val >= 0.0 && val < DARK_FOREST_THRESHOLD => Color::GREEN, 
-                    _ if val >= DARK_FOREST_THRESHOLD => Color::DARKGREEN, 
-                    _ => Color::DARKGREEN,
-                };
+                let color = biome_color(val);
                 if color == Color::BLUE || color == Color::DARKBLUE 
                 {
                     let mut color = color;
@@ -1419,25 +1111,7 @@ pub fn test_2D_Diamond(img_texture: &mut Image, img_water: &mut Image) -> Vec<Ve
     {
         for (j, &val) in row.iter().enumerate() 
         {
-            let color = match val 
-            {
-                -2.0 => Color::BLUE,
-                -1.0 => Color::DARKBLUE,
-                2.0 => Color::GOLD,
-                3.0 => Color::GRAY,
-                4.0 => Color::LIGHTGRAY, 
-                5.0 => Color::WHITE, 
-                6.0 => Color::YELLOW,
-                7.0 => Color::DARKGREEN,
-                8.0 => Color::PURPLE,
-                -5.0 => Color::BLACK,
-                10.0 => Color::RED,
-                11.0 => Color::ORANGE,
-                12.0 => Color::BLACK,
-                _ if val >= 0.0 && val < DARK_FOREST_THRESHOLD => Color::GREEN,
-                _ if val >= DARK_FOREST_THRESHOLD => Color::DARKGREEN,
-                _ => Color::DARKGREEN,
-            };
+            let color = biome_color(val);
             image.draw_pixel(j as i32, i as i32, color);
         }
     }
@@ -1455,24 +1129,7 @@ pub fn test_2D_Diamond(img_texture: &mut Image, img_water: &mut Image) -> Vec<Ve
         {
             for (j, &val) in row.iter().enumerate() 
             {
-                let color = match val {
-                    -2.0 => Color::BLUE, 
-                    -1.0 => Color::DARKBLUE,
-                    2.0 => Color::GOLD,
-                    3.0 => Color::GRAY, 
-                    4.0 => Color::LIGHTGRAY, 
-                    5.0 => Color::WHITE, 
-                    6.0 => Color::YELLOW,
-                    7.0 => Color::DARKGREEN,
-                    8.0 => Color::PURPLE,
-                    -5.0 => Color::BLACK,
-                    10.0 => Color::RED,
-                    11.0 => Color::ORANGE,
-                    12.0 => Color::BLACK,
-                    _ if val >= 0.0 && val < DARK_FOREST_THRESHOLD => Color::GREEN, 
-                    _ if val >= DARK_FOREST_THRESHOLD => Color::DARKGREEN, 
-                    _ => Color::DARKGREEN,
-                };
+                let color = biome_color(val);
                 if color == Color::BLUE || color == Color::DARKBLUE 
                 {
                     let mut color = color;
@@ -1527,25 +1184,7 @@ pub fn test_2D_MIX_Diamond_Perlin(img_texture: &mut Image, img_water: &mut Image
     {
         for (j, &val) in row.iter().enumerate() 
         {
-            let color = match val 
-            {
-                -2.0 => Color::BLUE,
-                -1.0 => Color::DARKBLUE,
-                2.0 => Color::GOLD,
-                3.0 => Color::GRAY,
-                4.0 => Color::LIGHTGRAY, 
-                5.0 => Color::WHITE, 
-                6.0 => Color::YELLOW,
-                7.0 => Color::DARKGREEN,
-                8.0 => Color::PURPLE,
-                -5.0 => Color::BLACK,
-                10.0 => Color::RED,
-                11.0 => Color::ORANGE,
-                12.0 => Color::BLACK,
-                _ if val >= 0.0 && val < DARK_FOREST_THRESHOLD => Color::GREEN,
-                _ if val >= DARK_FOREST_THRESHOLD => Color::DARKGREEN,
-                _ => Color::DARKGREEN,
-            };
+            let color = biome_color(val);
             image.draw_pixel(j as i32, i as i32, color);
         }
     }
@@ -1563,24 +1202,7 @@ pub fn test_2D_MIX_Diamond_Perlin(img_texture: &mut Image, img_water: &mut Image
         {
             for (j, &val) in row.iter().enumerate() 
             {
-                let color = match val {
-                    -2.0 => Color::BLUE, 
-                    -1.0 => Color::DARKBLUE,
-                    2.0 => Color::GOLD,
-                    3.0 => Color::GRAY, 
-                    4.0 => Color::LIGHTGRAY, 
-                    5.0 => Color::WHITE, 
-                    6.0 => Color::YELLOW,
-                    7.0 => Color::DARKGREEN,
-                    8.0 => Color::PURPLE,
-                    -5.0 => Color::BLACK,
-                    10.0 => Color::RED,
-                    11.0 => Color::ORANGE,
-                    12.0 => Color::BLACK,
-                    _ if val >= 0.0 && val < DARK_FOREST_THRESHOLD => Color::GREEN, 
-                    _ if val >= DARK_FOREST_THRESHOLD => Color::DARKGREEN, 
-                    _ => Color::DARKGREEN,
-                };
+                let color = biome_color(val);
                 if color == Color::BLUE || color == Color::DARKBLUE 
                 {
                     let mut color = color;
@@ -1802,25 +1424,7 @@ pub fn test_2D_WFC(img_texture: &mut Image, img_water: &mut Image) -> Vec<Vec<f6
     {
         for (j, &val) in row.iter().enumerate() 
         {
-            let color = match val 
-            {
-                -2.0 => Color::BLUE,
-                -1.0 => Color::DARKBLUE,
-                2.0 => Color::GOLD,
-                3.0 => Color::GRAY,
-                4.0 => Color::LIGHTGRAY, 
-                5.0 => Color::WHITE, 
-                6.0 => Color::YELLOW,
-                7.0 => Color::DARKGREEN,
-                8.0 => Color::PURPLE,
-                -5.0 => Color::BLACK,
-                10.0 => Color::RED,
-                11.0 => Color::ORANGE,
-                12.0 => Color::BLACK,
-                _ if val >= 0.0 && val < DARK_FOREST_THRESHOLD => Color::GREEN,
-                _ if val >= DARK_FOREST_THRESHOLD => Color::DARKGREEN,
-                _ => Color::DARKGREEN,
-            };
+            let color = biome_color(val);
             image.draw_pixel(j as i32, i as i32, color);
         }
     }
@@ -1838,24 +1442,7 @@ pub fn test_2D_WFC(img_texture: &mut Image, img_water: &mut Image) -> Vec<Vec<f6
         {
             for (j, &val) in row.iter().enumerate() 
             {
-                let color = match val {
-                    -2.0 => Color::BLUE, 
-                    -1.0 => Color::DARKBLUE,
-                    2.0 => Color::GOLD,
-                    3.0 => Color::GRAY, 
-                    4.0 => Color::LIGHTGRAY, 
-                    5.0 => Color::WHITE, 
-                    6.0 => Color::YELLOW,
-                    7.0 => Color::DARKGREEN,
-                    8.0 => Color::PURPLE,
-                    -5.0 => Color::BLACK,
-                    10.0 => Color::RED,
-                    11.0 => Color::ORANGE,
-                    12.0 => Color::BLACK,
-                    _ if val >= 0.0 && val < DARK_FOREST_THRESHOLD => Color::GREEN, 
-                    _ if val >= DARK_FOREST_THRESHOLD => Color::DARKGREEN, 
-                    _ => Color::DARKGREEN,
-                };
+                let color = biome_color(val);
                 if color == Color::BLUE || color == Color::DARKBLUE 
                 {
                     let mut color = color;
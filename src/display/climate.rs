@@ -0,0 +1,159 @@
+use super::build_2D::{apply_convolution, water_mask, BiomeOpts};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClimateOpts {
+    // direction the wind blows towards, in degrees (0 = east, 90 = south)
+    pub wind_direction: f64,
+    // moisture carried by the wind when it enters the map
+    pub boundary_moisture: f64,
+    // moisture picked up over each water cell
+    pub evaporation: f64,
+    // share of the carried moisture that rains over flat land
+    pub precipitation: f64,
+    // extra share that rains per unit of altitude climbed
+    pub orographic_lift: f64,
+    // humidity reported over water, only used by the marsh pass on coasts
+    pub water_humidity: f64,
+    pub sea_level: f64,
+    pub equator_temperature: f64,
+    pub pole_temperature: f64,
+    pub lapse_rate: f64,
+    // amplitude of the noise added on top of the latitude gradient
+    pub temperature_variation: f64,
+}
+
+impl Default for ClimateOpts {
+    fn default() -> Self {
+        Self {
+            wind_direction: 0.0,
+            boundary_moisture: 0.3,
+            evaporation: 0.2,
+            precipitation: 0.03,
+            orographic_lift: 1.5,
+            water_humidity: 0.5,
+            sea_level: 0.3,
+            equator_temperature: 0.8,
+            pole_temperature: 0.2,
+            lapse_rate: 0.5,
+            temperature_variation: 0.2,
+        }
+    }
+}
+
+// 0 on the equator (middle row), 1 on the poles
+fn latitude(row: usize, size: usize) -> f64 {
+    (row as f64 / (size - 1).max(1) as f64 - 0.5).abs() * 2.0
+}
+
+pub fn temperature_map(
+    altitude: &Vec<Vec<f64>>,
+    noise: &Vec<Vec<f64>>,
+    opts: &ClimateOpts,
+) -> Vec<Vec<f64>> {
+    let size = altitude.len();
+    let mut res = vec![vec![0.0; size]; size];
+
+    for i in 0..size {
+        let lat = latitude(i, size);
+        let base = opts.equator_temperature
+            - (opts.equator_temperature - opts.pole_temperature) * lat;
+
+        for j in 0..size {
+            let height = (altitude[i][j] - opts.sea_level).max(0.0);
+            let variation = (noise[i][j] - 0.5) * opts.temperature_variation;
+
+            res[i][j] =
+                (base + variation - opts.lapse_rate * height).clamp(0.0, 1.0);
+        }
+    }
+
+    res
+}
+
+// Moisture is advected cell by cell along the wind: water bodies load the
+// air, land takes a share of it as rain, and climbing slopes forces more rain
+// out, leaving a rain shadow behind mountain ranges.
+pub fn humidity_map(
+    altitude: &Vec<Vec<f64>>,
+    opts: &ClimateOpts,
+) -> Vec<Vec<f64>> {
+    let size = altitude.len();
+    let water = water_mask(
+        altitude,
+        &BiomeOpts {
+            deep_water: opts.sea_level,
+            ..BiomeOpts::default()
+        },
+    );
+
+    let angle = opts.wind_direction.to_radians();
+    let (wx, wy) = (angle.cos(), angle.sin());
+
+    // cells sorted along the wind so that the upwind cell is always known
+    let mut order: Vec<(usize, usize)> = (0..size)
+        .flat_map(|i| (0..size).map(move |j| (i, j)))
+        .collect();
+    order.sort_by(|a, b| {
+        let pa = a.1 as f64 * wx + a.0 as f64 * wy;
+        let pb = b.1 as f64 * wx + b.0 as f64 * wy;
+        pa.total_cmp(&pb)
+    });
+
+    let mut moisture = vec![vec![0.0; size]; size];
+    let mut rain = vec![vec![0.0; size]; size];
+
+    for (i, j) in order {
+        let ui = (i as f64 - wy).round();
+        let uj = (j as f64 - wx).round();
+        let upwind = if ui >= 0.0
+            && uj >= 0.0
+            && (ui as usize) < size
+            && (uj as usize) < size
+        {
+            Some((ui as usize, uj as usize))
+        } else {
+            None
+        };
+
+        let (incoming, previous_altitude) = match upwind {
+            Some((ui, uj)) => (moisture[ui][uj], altitude[ui][uj]),
+            None => (opts.boundary_moisture, altitude[i][j]),
+        };
+
+        if water[i][j] {
+            moisture[i][j] = incoming + opts.evaporation * (1.0 - incoming);
+            continue;
+        }
+
+        let climb = (altitude[i][j].max(opts.sea_level)
+            - previous_altitude.max(opts.sea_level))
+        .max(0.0);
+        let share =
+            (opts.precipitation + opts.orographic_lift * climb).min(1.0);
+        let fall = incoming * share;
+
+        rain[i][j] = fall;
+        moisture[i][j] = incoming - fall;
+    }
+
+    // rain is proportional to the moisture left, rescale it on land only
+    let max_rain = (0..size)
+        .flat_map(|i| (0..size).map(move |j| (i, j)))
+        .filter(|&(i, j)| !water[i][j])
+        .map(|(i, j)| rain[i][j])
+        .fold(0.0, f64::max);
+
+    let mut res = vec![vec![opts.water_humidity; size]; size];
+    for i in 0..size {
+        for j in 0..size {
+            if !water[i][j] && max_rain > 0.0 {
+                res[i][j] = (rain[i][j] / max_rain).sqrt();
+            }
+        }
+    }
+
+    apply_convolution(&mut res);
+    res
+}
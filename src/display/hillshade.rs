@@ -0,0 +1,149 @@
+// Shaded relief for heightmaps in [0, 1], `heights[i][j]` with i along the
+// image x axis and j along the image y axis.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HillshadeOpts {
+    // direction the light comes from, in degrees clockwise from north (-y)
+    pub azimuth: f64,
+    // height of the sun above the horizon, in degrees
+    pub elevation: f64,
+    // altitude 1.0 expressed in cells
+    pub z_scale: f64,
+    // light received by the faces turned away from the sun
+    pub ambient: f64,
+    pub ambient_occlusion: bool,
+    // distance, in cells, scanned for occluders
+    pub ao_radius: usize,
+    pub ao_strength: f64,
+}
+
+impl Default for HillshadeOpts {
+    fn default() -> Self {
+        Self {
+            azimuth: 315.0,
+            elevation: 45.0,
+            z_scale: 64.0,
+            ambient: 0.35,
+            ambient_occlusion: false,
+            ao_radius: 12,
+            ao_strength: 0.6,
+        }
+    }
+}
+
+const DIRECTIONS: [(isize, isize); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+fn at(heights: &Vec<Vec<f64>>, i: isize, j: isize) -> f64 {
+    let w = heights.len() as isize;
+    let h = heights[0].len() as isize;
+    heights[i.clamp(0, w - 1) as usize][j.clamp(0, h - 1) as usize]
+}
+
+// Lambertian light, in [0, 1]
+pub fn lambert(heights: &Vec<Vec<f64>>, opts: &HillshadeOpts) -> Vec<Vec<f64>> {
+    let azimuth = opts.azimuth.to_radians();
+    let elevation = opts.elevation.to_radians();
+    // x = i (east), y = j (south), z = up
+    let light = (
+        elevation.cos() * azimuth.sin(),
+        -elevation.cos() * azimuth.cos(),
+        elevation.sin(),
+    );
+
+    (0..heights.len())
+        .map(|i| {
+            (0..heights[i].len())
+                .map(|j| {
+                    let (i, j) = (i as isize, j as isize);
+                    let dx = (at(heights, i + 1, j) - at(heights, i - 1, j))
+                        * opts.z_scale
+                        / 2.0;
+                    let dy = (at(heights, i, j + 1) - at(heights, i, j - 1))
+                        * opts.z_scale
+                        / 2.0;
+
+                    // normal of the surface z = h(x, y) is (-dx, -dy, 1)
+                    let norm = (dx * dx + dy * dy + 1.0).sqrt();
+                    let diffuse =
+                        (-dx * light.0 - dy * light.1 + light.2) / norm;
+
+                    opts.ambient + (1.0 - opts.ambient) * diffuse.max(0.0)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Horizon based occlusion: for each of 8 directions keep the highest angle
+// to the horizon, the visible part of the sky is what remains above it.
+pub fn ambient_occlusion(
+    heights: &Vec<Vec<f64>>,
+    opts: &HillshadeOpts,
+) -> Vec<Vec<f64>> {
+    (0..heights.len())
+        .map(|i| {
+            (0..heights[i].len())
+                .map(|j| {
+                    let h = heights[i][j];
+                    let (i, j) = (i as isize, j as isize);
+
+                    let occlusion: f64 = DIRECTIONS
+                        .iter()
+                        .map(|&(di, dj)| {
+                            let step = ((di * di + dj * dj) as f64).sqrt();
+                            let horizon = (1..=opts.ao_radius as isize)
+                                .map(|k| {
+                                    let rise =
+                                        (at(heights, i + di * k, j + dj * k)
+                                            - h)
+                                            * opts.z_scale;
+                                    rise / (k as f64 * step)
+                                })
+                                .fold(0.0, f64::max);
+
+                            horizon.atan().sin()
+                        })
+                        .sum::<f64>()
+                        / DIRECTIONS.len() as f64;
+
+                    1.0 - opts.ao_strength * occlusion
+                })
+                .collect()
+        })
+        .collect()
+}
+
+pub fn hillshade(
+    heights: &Vec<Vec<f64>>,
+    opts: &HillshadeOpts,
+) -> Vec<Vec<f64>> {
+    if heights.is_empty() || heights[0].is_empty() {
+        return Vec::new();
+    }
+
+    let mut res = lambert(heights, opts);
+
+    if opts.ambient_occlusion {
+        let ao = ambient_occlusion(heights, opts);
+        res.iter_mut().zip(ao.iter()).for_each(|(row, ao_row)| {
+            row.iter_mut().zip(ao_row.iter()).for_each(|(x, a)| *x *= a)
+        });
+    }
+
+    res
+}
+
+// scales an rgb channel by the light, flat ground under the default sun
+// keeps roughly its colour
+pub fn shade_channel(channel: u8, light: f64) -> u8 {
+    (channel as f64 * light * 1.25).clamp(0.0, 255.0) as u8
+}
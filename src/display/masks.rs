@@ -0,0 +1,200 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MaskShape {
+    Radial,
+    Square,
+    MultiIsland,
+    Polygon,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MaskBlend {
+    // altitude * mask, the shape carves the terrain down to the sea
+    Multiply,
+    // altitude pulled towards the mask, the shape also raises the land
+    Blend,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaskOpts {
+    pub shape: MaskShape,
+    pub blend: MaskBlend,
+    // half extent of the flat land, as a fraction of the half map size
+    pub radius: f64,
+    // width of the coast slope, as a fraction of the half map size
+    pub falloff: f64,
+    pub strength: f64,
+    pub islands: usize,
+    pub seed: u64,
+    // polygon vertices in [0, 1] map coordinates, (row, column)
+    pub points: Vec<(f64, f64)>,
+}
+
+impl Default for MaskOpts {
+    fn default() -> Self {
+        Self {
+            shape: MaskShape::Radial,
+            blend: MaskBlend::Multiply,
+            radius: 0.4,
+            falloff: 0.4,
+            strength: 1.0,
+            islands: 4,
+            seed: 0,
+            points: vec![(0.2, 0.3), (0.3, 0.8), (0.7, 0.75), (0.8, 0.25)],
+        }
+    }
+}
+
+fn smoothstep(x: f64) -> f64 {
+    let x = x.clamp(0.0, 1.0);
+    x * x * (3.0 - 2.0 * x)
+}
+
+// 1 up to `radius`, then eases down to 0 over `falloff`
+fn falloff(distance: f64, radius: f64, falloff: f64) -> f64 {
+    if distance <= radius {
+        return 1.0;
+    }
+    if falloff <= 0.0 {
+        return 0.0;
+    }
+
+    1.0 - smoothstep((distance - radius) / falloff)
+}
+
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len = dx * dx + dy * dy;
+    let t = if len > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    ((p.0 - a.0 - t * dx).powi(2) + (p.1 - a.1 - t * dy).powi(2)).sqrt()
+}
+
+fn polygon_contains(p: (f64, f64), points: &[(f64, f64)]) -> bool {
+    let mut inside = false;
+    let mut j = points.len() - 1;
+
+    for i in 0..points.len() {
+        let (a, b) = (points[i], points[j]);
+        if (a.1 > p.1) != (b.1 > p.1)
+            && p.0 < (b.0 - a.0) * (p.1 - a.1) / (b.1 - a.1) + a.0
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+
+    inside
+}
+
+// distance to the polygon, 0 inside it
+fn polygon_distance(p: (f64, f64), points: &[(f64, f64)]) -> f64 {
+    if points.len() < 3 {
+        return f64::MAX;
+    }
+    if polygon_contains(p, points) {
+        return 0.0;
+    }
+
+    (0..points.len())
+        .map(|i| segment_distance(p, points[i], points[(i + 1) % points.len()]))
+        .fold(f64::MAX, f64::min)
+}
+
+// island centers and radii in [0, 1] map coordinates
+fn islands(opts: &MaskOpts) -> Vec<((f64, f64), f64)> {
+    let mut rng = StdRng::seed_from_u64(opts.seed);
+
+    (0..opts.islands.max(1))
+        .map(|_| {
+            let center = (rng.gen_range(0.2..0.8), rng.gen_range(0.2..0.8));
+            let scale = rng.gen_range(0.3..0.6);
+            (center, scale)
+        })
+        .collect()
+}
+
+pub fn generate_mask(size: usize, opts: &MaskOpts) -> Vec<Vec<f64>> {
+    if size == 0 {
+        return Vec::new();
+    }
+
+    let mut res = vec![vec![0.0; size]; size];
+    let last = (size - 1).max(1) as f64;
+    let islands = islands(opts);
+
+    for i in 0..size {
+        for j in 0..size {
+            let p = (i as f64 / last, j as f64 / last);
+            // distances are expressed in half map sizes
+            let (di, dj) = ((p.0 - 0.5) * 2.0, (p.1 - 0.5) * 2.0);
+
+            res[i][j] = match opts.shape {
+                MaskShape::Radial => falloff(
+                    (di * di + dj * dj).sqrt(),
+                    opts.radius,
+                    opts.falloff,
+                ),
+                MaskShape::Square => {
+                    falloff(di.abs().max(dj.abs()), opts.radius, opts.falloff)
+                }
+                MaskShape::MultiIsland => islands
+                    .iter()
+                    .map(|&(c, scale)| {
+                        let d = ((p.0 - c.0).powi(2) + (p.1 - c.1).powi(2))
+                            .sqrt()
+                            * 2.0;
+                        falloff(d, opts.radius * scale, opts.falloff * scale)
+                    })
+                    .fold(0.0, f64::max),
+                MaskShape::Polygon => falloff(
+                    polygon_distance(p, &opts.points) * 2.0,
+                    0.0,
+                    opts.falloff,
+                ),
+            };
+        }
+    }
+
+    // nothing is kept on the border
+    for i in 0..size {
+        for j in [0, size - 1] {
+            res[i][j] = 0.0;
+            res[j][i] = 0.0;
+        }
+    }
+
+    res
+}
+
+pub fn apply_mask(altitude: &mut Vec<Vec<f64>>, opts: &MaskOpts) {
+    let mask = generate_mask(altitude.len(), opts);
+    let strength = opts.strength.clamp(0.0, 1.0);
+
+    for (row, mask_row) in altitude.iter_mut().zip(mask.iter()) {
+        for (value, m) in row.iter_mut().zip(mask_row.iter()) {
+            let shaped = match opts.blend {
+                MaskBlend::Multiply => *value * m,
+                MaskBlend::Blend => (*value + m) / 2.0,
+            };
+            *value += (shaped - *value) * strength;
+        }
+    }
+
+    // the border always ends up under the sea, whatever the blend and the
+    // strength, so that the islands are surrounded by it
+    let size = altitude.len();
+    for i in 0..size {
+        for j in [0, size - 1] {
+            altitude[i][j] = 0.0;
+            altitude[j][i] = 0.0;
+        }
+    }
+}
@@ -1,2 +1,7 @@
 pub mod build_2D;
+pub mod climate;
 pub mod display_3D;
+pub mod hillshade;
+pub mod masks;
+pub mod names;
+pub mod settlements;
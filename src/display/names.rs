@@ -0,0 +1,136 @@
+// Place names made up by a Markov chain on the letters of a list of real
+// ones. The list is read once from `names.txt`, one name per line, and a
+// built-in one is used when there is no such file or when it has no name.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::OnceLock;
+
+const WORD_LIST: &str = "./names.txt";
+const DEFAULT_WORDS: &str = "Aubenas\nAurillac\nBeaumont\nBelfort\nBergerac\n\
+    Besancon\nBrignoles\nCahors\nCarcassonne\nCastelnau\nChamonix\nChartres\n\
+    Clermont\nCognac\nColmar\nDinan\nDouarnenez\nEpernay\nFigeac\nFontenay\n\
+    Gaillac\nGrenoble\nGueret\nLannion\nLaval\nLimoges\nLodeve\nLorient\n\
+    Lourdes\nMarmande\nMartigues\nMenton\nMillau\nMontauban\nMontbrison\n\
+    Montelimar\nMorlaix\nNarbonne\nNevers\nNiort\nOrange\nOrthez\nPamiers\n\
+    Perigueux\nPontarlier\nQuimper\nRoanne\nRodez\nRoquefort\nSarlat\nSaumur\n\
+    Sedan\nSoissons\nTarascon\nThiers\nTournon\nTulle\nUzes\nValence\nVannes\n\
+    Vendome\nVerdun\nVichy\nVienne\nVierzon\nVillefranche\nVilleneuve\nVitre";
+// letters looked at to pick the next one
+const ORDER: usize = 2;
+const MIN_LENGTH: usize = 4;
+const MAX_LENGTH: usize = 12;
+// names generated before giving up on finding a new one
+const MAX_TRIES: usize = 100;
+const START: char = '^';
+const END: char = '$';
+
+static GENERATOR: OnceLock<NameGenerator> = OnceLock::new();
+
+pub struct NameGenerator {
+    // letters seen after each run of `ORDER` letters, with repetitions so
+    // that they are picked as often as they appear
+    chains: HashMap<Vec<char>, Vec<char>>,
+    // the list is only a model, its names aren't given back
+    words: HashSet<String>,
+}
+
+impl NameGenerator {
+    pub fn new<'a>(words: impl Iterator<Item = &'a str>) -> Self {
+        let mut chains = HashMap::<Vec<char>, Vec<char>>::new();
+        let mut known = HashSet::new();
+
+        for word in words.map(|x| x.trim().to_lowercase()) {
+            if word.is_empty() {
+                continue;
+            }
+
+            let letters: Vec<char> = std::iter::repeat_n(START, ORDER)
+                .chain(word.chars())
+                .chain(std::iter::once(END))
+                .collect();
+
+            letters.windows(ORDER + 1).for_each(|x| {
+                chains
+                    .entry(x[..ORDER].to_vec())
+                    .or_default()
+                    .push(x[ORDER]);
+            });
+            known.insert(word);
+        }
+
+        Self {
+            chains,
+            words: known,
+        }
+    }
+
+    // trained on the user's list, or on the built-in one, the first time
+    // only
+    pub fn load() -> &'static Self {
+        GENERATOR.get_or_init(|| {
+            fs::read_to_string(WORD_LIST)
+                .map(|x| Self::new(x.lines()))
+                .ok()
+                .filter(|x| !x.chains.is_empty())
+                .unwrap_or_else(|| Self::new(DEFAULT_WORDS.lines()))
+        })
+    }
+
+    // A name that is neither in the list nor in `taken`, `None` when none
+    // could be found, which happens with lists too short.
+    pub fn generate(
+        &self,
+        rng: &mut impl Rng,
+        taken: &HashSet<String>,
+    ) -> Option<String> {
+        (0..MAX_TRIES).find_map(|_| {
+            let word = self.walk(rng)?;
+            let length = word.chars().count();
+
+            if !(MIN_LENGTH..=MAX_LENGTH).contains(&length)
+                || self.words.contains(&word)
+            {
+                return None;
+            }
+
+            Some(capitalize(&word)).filter(|x| !taken.contains(x))
+        })
+    }
+
+    fn walk(&self, rng: &mut impl Rng) -> Option<String> {
+        let mut key = vec![START; ORDER];
+        let mut res = String::new();
+
+        loop {
+            let next = *self.chains.get(&key)?.choose(rng)?;
+
+            if next == END || res.len() > MAX_LENGTH * 4 {
+                return Some(res);
+            }
+
+            res.push(next);
+            key.remove(0);
+            key.push(next);
+        }
+    }
+}
+
+// upper case at the start of every part of the name
+fn capitalize(word: &str) -> String {
+    let mut res = String::new();
+    let mut upper = true;
+
+    for x in word.chars() {
+        if upper {
+            res.extend(x.to_uppercase());
+        } else {
+            res.push(x);
+        }
+        upper = x == ' ' || x == '-';
+    }
+
+    res
+}
@@ -0,0 +1,402 @@
+// Settlements placed on a biome map and the roads joining them. Both write
+// their biome code into the map and return what they placed, so that it can
+// be used without reading the map again.
+
+use super::build_2D::{
+    CITY, DARK_FOREST, DEEP_WATER, DESSERT, DESSERT_SHARA, GLACIAL, GRASSLAND,
+    MARSH, MOUNTAIN_SNOW, MOUTAIN, RAINFOREST, ROUTE, SAVANNA, SHRUBLAND,
+    TAIGA, TEMPERATE_FOREST, TROPICAL_FOREST, TUNDRA, VILLAGE, WATER,
+};
+use super::names::NameGenerator;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+// biomes settlements and roads can be restricted to, with their name
+pub const BIOMES: [(&str, f64); 17] = [
+    ("Deep water", DEEP_WATER),
+    ("Water", WATER),
+    ("Desert", DESSERT),
+    ("Mountain", MOUTAIN),
+    ("Snow", MOUNTAIN_SNOW),
+    ("Glacial", GLACIAL),
+    ("Sahara", DESSERT_SHARA),
+    ("Dark forest", DARK_FOREST),
+    ("Marsh", MARSH),
+    ("Tundra", TUNDRA),
+    ("Taiga", TAIGA),
+    ("Temperate", TEMPERATE_FOREST),
+    ("Grassland", GRASSLAND),
+    ("Shrubland", SHRUBLAND),
+    ("Savanna", SAVANNA),
+    ("Tropical", TROPICAL_FOREST),
+    ("Rainforest", RAINFOREST),
+];
+
+// densities are given per this many cells of allowed biomes
+const DENSITY_AREA: f64 = 10000.0;
+// length of the arms of the cross drawn for a settlement
+const SETTLEMENT_ARM: usize = 2;
+// sideways shift of the control point of a road, per cell of its length
+const ROAD_BEND: f64 = 0.15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettlementKind {
+    City,
+    Village,
+}
+
+impl SettlementKind {
+    // biome code written in the map
+    pub fn value(self) -> f64 {
+        match self {
+            SettlementKind::City => CITY,
+            SettlementKind::Village => VILLAGE,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SettlementKind::City => "City",
+            SettlementKind::Village => "Village",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settlement {
+    pub kind: SettlementKind,
+    pub pos: (usize, usize),
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Road {
+    // indices in the list of settlements
+    pub from: usize,
+    pub to: usize,
+    pub path: Vec<(usize, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettlementOpts {
+    // settlements per 10 000 cells of allowed biomes
+    pub city_density: f64,
+    pub village_density: f64,
+    // smallest distance between two settlements, in cells
+    pub min_spacing: f64,
+    // biome codes settlements can be built on
+    pub allowed: Vec<f64>,
+    pub seed: u64,
+}
+
+impl Default for SettlementOpts {
+    fn default() -> Self {
+        Self {
+            city_density: 0.625,
+            village_density: 1.25,
+            min_spacing: 20.0,
+            allowed: vec![GRASSLAND, SHRUBLAND, SAVANNA, TEMPERATE_FOREST],
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoadOpts {
+    // longest road leaving a city, and one touching a village, in cells
+    pub city_distance: f64,
+    pub village_distance: f64,
+    // roads leaving a city, and a village
+    pub city_connections: usize,
+    pub village_connections: usize,
+    // biome codes roads can't cross
+    pub forbidden: Vec<f64>,
+}
+
+impl Default for RoadOpts {
+    fn default() -> Self {
+        Self {
+            city_distance: 200.0,
+            village_distance: 100.0,
+            city_connections: 5,
+            village_connections: 3,
+            forbidden: vec![
+                DEEP_WATER,
+                WATER,
+                DESSERT,
+                MOUTAIN,
+                MOUNTAIN_SNOW,
+                GLACIAL,
+                DESSERT_SHARA,
+                MARSH,
+            ],
+        }
+    }
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+    rank: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+            rank: vec![0; n],
+        }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        if self.parent[x] != x {
+            self.parent[x] = self.find(self.parent[x]);
+        }
+        self.parent[x]
+    }
+
+    fn union(&mut self, x: usize, y: usize) {
+        let root_x = self.find(x);
+        let root_y = self.find(y);
+
+        if root_x == root_y {
+            return;
+        }
+
+        if self.rank[root_x] < self.rank[root_y] {
+            self.parent[root_x] = root_y;
+        } else {
+            self.parent[root_y] = root_x;
+            if self.rank[root_x] == self.rank[root_y] {
+                self.rank[root_x] += 1;
+            }
+        }
+    }
+}
+
+fn distance2((x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> f64 {
+    let dx = x1 as f64 - x2 as f64;
+    let dy = y1 as f64 - y2 as f64;
+    dx * dx + dy * dy
+}
+
+// the cross drawn for a settlement fits in the map
+fn fits(grid: &[Vec<f64>], (i, j): (usize, usize)) -> bool {
+    i >= SETTLEMENT_ARM
+        && j >= SETTLEMENT_ARM
+        && i + SETTLEMENT_ARM < grid.len()
+        && j + SETTLEMENT_ARM < grid[i].len()
+}
+
+fn cross((i, j): (usize, usize)) -> impl Iterator<Item = (usize, usize)> {
+    (1..=SETTLEMENT_ARM)
+        .flat_map(move |d| [(i - d, j), (i + d, j), (i, j - d), (i, j + d)])
+        .chain(std::iter::once((i, j)))
+}
+
+// Places the cities then the villages on cells of the allowed biomes, as
+// many as the densities give for their area, at random but never closer
+// than the spacing to each other.
+pub fn place_settlements(
+    grid: &mut [Vec<f64>],
+    opts: &SettlementOpts,
+) -> Vec<Settlement> {
+    let mut candidates: Vec<(usize, usize)> = (0..grid.len())
+        .flat_map(|i| (0..grid[i].len()).map(move |j| (i, j)))
+        .filter(|(i, j)| opts.allowed.contains(&grid[*i][*j]))
+        .collect();
+
+    let area = candidates.len() as f64 / DENSITY_AREA;
+    let counts = [
+        (SettlementKind::City, area * opts.city_density),
+        (SettlementKind::Village, area * opts.village_density),
+    ];
+
+    candidates.retain(|x| fits(grid, *x));
+    candidates.shuffle(&mut StdRng::seed_from_u64(opts.seed));
+
+    let spacing = opts.min_spacing * opts.min_spacing;
+    let mut candidates = candidates.into_iter();
+    let mut res = Vec::<Settlement>::new();
+
+    for (kind, count) in counts {
+        let count = count.round() as usize;
+        let mut placed = 0;
+
+        while placed < count {
+            let pos = match candidates.next() {
+                Some(pos) => pos,
+                None => break,
+            };
+
+            if res.iter().all(|x| distance2(x.pos, pos) >= spacing) {
+                res.push(Settlement {
+                    kind,
+                    pos,
+                    name: String::new(),
+                });
+                placed += 1;
+            }
+        }
+    }
+
+    res.iter().for_each(|x| {
+        cross(x.pos).for_each(|(i, j)| grid[i][j] = x.kind.value());
+    });
+
+    name_settlements(&mut res, opts.seed);
+    res
+}
+
+// Names made up from the word list, the same for the same seed. The ones
+// left when no new name can be found are numbered in their kind.
+fn name_settlements(settlements: &mut [Settlement], seed: u64) {
+    let generator = NameGenerator::load();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut taken = HashSet::new();
+    let mut counts = [0, 0];
+
+    settlements.iter_mut().for_each(|x| {
+        let count = &mut counts[x.kind as usize];
+        *count += 1;
+
+        x.name = generator
+            .generate(&mut rng, &taken)
+            .unwrap_or_else(|| format!("{} {}", x.kind.name(), count));
+        taken.insert(x.name.clone());
+    });
+}
+
+fn bezier_curve(
+    start: (usize, usize),
+    control: (usize, usize),
+    end: (usize, usize),
+) -> Vec<(usize, usize)> {
+    // about two points per cell so that the road has no gap, the curve is
+    // never longer than its control polygon
+    let length =
+        distance2(start, control).sqrt() + distance2(control, end).sqrt();
+    let steps = (length * 2.0).ceil().max(1.0) as usize;
+    let point = |a: usize, b: usize, c: usize, t: f64| {
+        ((1.0 - t) * (1.0 - t) * a as f64
+            + 2.0 * (1.0 - t) * t * b as f64
+            + t * t * c as f64)
+            .round() as usize
+    };
+
+    let mut path: Vec<(usize, usize)> = (0..=steps)
+        .map(|x| {
+            let t = x as f64 / steps as f64;
+            (
+                point(start.0, control.0, end.0, t),
+                point(start.1, control.1, end.1, t),
+            )
+        })
+        .collect();
+
+    path.dedup();
+    path
+}
+
+// Middle of the road shifted sideways, to the left or the right of it. It is
+// kept in the map so that the curve is too.
+fn control_point(
+    grid: &[Vec<f64>],
+    start: (usize, usize),
+    end: (usize, usize),
+    left: bool,
+) -> (usize, usize) {
+    let side = if left { ROAD_BEND } else { -ROAD_BEND };
+    let dx = end.0 as f64 - start.0 as f64;
+    let dy = end.1 as f64 - start.1 as f64;
+    let x = (start.0 + end.0) as f64 / 2.0 - dy * side;
+    let y = (start.1 + end.1) as f64 / 2.0 + dx * side;
+
+    (
+        x.round().clamp(0.0, (grid.len() - 1) as f64) as usize,
+        y.round().clamp(0.0, (grid[0].len() - 1) as f64) as usize,
+    )
+}
+
+// Joins the settlements with a spanning forest, shortest roads first. A road
+// is dropped when it is too long, when one of its ends has all of its
+// connections or when it crosses a forbidden biome.
+pub fn build_roads(
+    grid: &mut [Vec<f64>],
+    settlements: &[Settlement],
+    opts: &RoadOpts,
+) -> Vec<Road> {
+    let n = settlements.len();
+    let mut edges = Vec::new();
+
+    for a in 0..n {
+        for b in a + 1..n {
+            let village = settlements[a].kind == SettlementKind::Village
+                || settlements[b].kind == SettlementKind::Village;
+            let max = if village {
+                opts.village_distance
+            } else {
+                opts.city_distance
+            };
+            let distance = distance2(settlements[a].pos, settlements[b].pos);
+
+            if distance <= max * max {
+                edges.push((distance, a, b, village));
+            }
+        }
+    }
+
+    edges.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    let mut uf = UnionFind::new(n);
+    let mut connections = vec![0; n];
+    let mut res = Vec::new();
+
+    for (_, a, b, village) in edges {
+        let max = if village {
+            opts.village_connections
+        } else {
+            opts.city_connections
+        };
+
+        if uf.find(a) == uf.find(b)
+            || connections[a] >= max
+            || connections[b] >= max
+        {
+            continue;
+        }
+
+        let start = settlements[a].pos;
+        let end = settlements[b].pos;
+        let control = control_point(grid, start, end, (a + b) % 2 == 0);
+        let path = bezier_curve(start, control, end);
+
+        if path
+            .iter()
+            .any(|(i, j)| opts.forbidden.contains(&grid[*i][*j]))
+        {
+            continue;
+        }
+
+        uf.union(a, b);
+        connections[a] += 1;
+        connections[b] += 1;
+        res.push(Road {
+            from: a,
+            to: b,
+            path,
+        });
+    }
+
+    res.iter().flat_map(|x| x.path.iter()).for_each(|(i, j)| {
+        if grid[*i][*j] != CITY && grid[*i][*j] != VILLAGE {
+            grid[*i][*j] = ROUTE;
+        }
+    });
+
+    res
+}
@@ -2,6 +2,7 @@ pub mod diamond_square;
 pub mod display;
 pub mod obj_export;
 pub mod perlin_noise;
+pub mod terrain_analysis;
 pub mod ui;
 
 use ui::*;
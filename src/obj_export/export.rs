@@ -22,9 +22,9 @@ pub fn export_obj(mesh: &Mesh, filename: &String) {
     file.write_all(b"\n# Triangles\n").expect("Error while writing triangles");
 
     for i in &mesh.indices {
-        file.write(format!("f {}/{}/{} {}/{}/{} {}/{}/{}\n", 
-                i.0+1, 1, 1,
-                i.1+1, 1, 1,
-                i.2+1, 1, 1).as_bytes()).unwrap();
+        file.write(format!("f {0}//{0} {1}//{1} {2}//{2}\n",
+                i.0+1,
+                i.1+1,
+                i.2+1).as_bytes()).unwrap();
     }
 }
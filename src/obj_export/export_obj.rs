@@ -1,13 +1,332 @@
+use crate::obj_export::gltf::gltf::Texture;
 use crate::obj_export::mesh::mesh::Mesh;
+use crate::obj_export::writer::writer::{create_file, ExportError, Surface};
 
+use serde_json::json;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// the skirts keep their own normals instead of bending the terrain's ones
+const SKIRT_EDGE_ANGLE: f64 = 60.0;
+
+// hard edges of a terrain, sharp enough for the skirts when it has some
+fn edge_angle(skirts: bool, hard_edge_angle: Option<f64>) -> Option<f64> {
+    match hard_edge_angle {
+        Some(angle) if skirts => Some(angle.min(SKIRT_EDGE_ANGLE)),
+        None if skirts => Some(SKIRT_EDGE_ANGLE),
+        _ => hard_edge_angle,
+    }
+}
+
+// image at `texture`, relative to the directory of `path`
+fn texture_path(
+    path: &str,
+    texture: Option<&str>,
+) -> Result<Option<PathBuf>, ExportError> {
+    let texture = match texture {
+        Some(texture) => texture,
+        None => return Ok(None),
+    };
+    let res = Path::new(path)
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(texture);
+
+    if !res.is_file() {
+        return Err(ExportError::MissingTexture(res.display().to_string()));
+    }
+
+    Ok(Some(res))
+}
+
+// `texture` is an image path relative to `path`, used as the diffuse map
 pub fn create_3d_terrain(
     res_w: usize,
     res_h: usize,
     heightmap: Vec<Vec<f64>>,
     path: &str,
     max_height: f64,
-) -> Result<(), String> {
+    texture: Option<&str>,
+) -> Result<(), ExportError> {
+    let new_height_map = resize_mat(&heightmap, res_w, res_h, max_height);
+    let mesh = Mesh::terrain(
+        heightmap[0].len() as f64,
+        heightmap.len() as f64,
+        res_w - 1,
+        res_h - 1,
+        new_height_map,
+    );
+    texture_path(path, texture)?;
+    mesh.to_obj(path, texture)
+}
+
+// same as `create_3d_terrain` with an error-bounded simplified mesh,
+// `tolerance` is expressed in the exported height unit
+pub fn create_simplified_3d_terrain(
+    res_w: usize,
+    res_h: usize,
+    heightmap: Vec<Vec<f64>>,
+    path: &str,
+    max_height: f64,
+    tolerance: f64,
+    texture: Option<&str>,
+) -> Result<(), ExportError> {
+    let new_height_map = resize_mat(&heightmap, res_w, res_h, max_height);
+    let mesh = Mesh::simplified_terrain(
+        heightmap[0].len() as f64,
+        heightmap.len() as f64,
+        res_w - 1,
+        res_h - 1,
+        new_height_map,
+        tolerance,
+        None,
+    );
+    texture_path(path, texture)?;
+    mesh.to_obj(path, texture)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SceneOpts {
+    // water cells, indexed like the heightmap, and the sea level in [0, 1]
+    pub water: Option<(Vec<Vec<bool>>, f64)>,
+    // depth of the skirts hanging under the borders, in the exported height
+    // unit, they hide the cracks between neighbouring chunks
+    pub skirt_depth: Option<f64>,
+    // faces meeting at a sharper angle, in degrees, don't share their
+    // normals, the terrain is smooth everywhere without it
+    pub hard_edge_angle: Option<f64>,
+}
+
+// terrain and, with `opts`, a separate water object, written as OBJ, glTF
+// or GLB depending on the extension of `path`. The png at `texture`,
+// relative to `path`, is embedded in a .glb and referenced by the others
+pub fn create_3d_scene(
+    res_w: usize,
+    res_h: usize,
+    heightmap: Vec<Vec<f64>>,
+    path: &str,
+    max_height: f64,
+    texture: Option<&str>,
+    opts: &SceneOpts,
+) -> Result<(), ExportError> {
+    let new_height_map = resize_mat(&heightmap, res_w, res_h, max_height);
+    let width = heightmap[0].len() as f64;
+    let height = heightmap.len() as f64;
+    let mut terrain = Mesh::grid_terrain(
+        width,
+        height,
+        res_w - 1,
+        res_h - 1,
+        new_height_map,
+        true,
+        opts.hard_edge_angle,
+    );
+
+    if let Some(depth) = opts.skirt_depth {
+        terrain = terrain.welded();
+        terrain.add_skirts(|v| v.y - depth);
+        terrain.compute_normals(edge_angle(true, opts.hard_edge_angle));
+    }
+
+    let water = opts.water.as_ref().map(|(mask, level)| {
+        Mesh::water(
+            width,
+            height,
+            res_w - 1,
+            res_h - 1,
+            &resample(mask, res_w, res_h),
+            level * max_height,
+        )
+    });
+
+    // a map without water has no water object, an empty mesh isn't valid
+    // glTF
+    let mut scene = vec![(Surface::Terrain, &terrain)];
+    if let Some(water) = water.as_ref().filter(|x| !x.indices.is_empty()) {
+        scene.push((Surface::Water, water));
+    }
+
+    write_scene(path, &scene, texture)
+}
+
+#[derive(Clone, Debug)]
+pub struct ChunkOpts {
+    // cells per tile side, a multiple of 2^(lods - 1) keeps the borders of
+    // neighbouring tiles identical at every level
+    pub tile_size: usize,
+    // level l keeps one grid line out of 2^l
+    pub lods: usize,
+    // skirts hide the cracks between tiles at different levels
+    pub skirt_depth: Option<f64>,
+    // same as `SceneOpts::hard_edge_angle`
+    pub hard_edge_angle: Option<f64>,
+}
+
+impl Default for ChunkOpts {
+    fn default() -> Self {
+        Self {
+            tile_size: 64,
+            lods: 3,
+            skirt_depth: Some(4.0),
+            hard_edge_angle: None,
+        }
+    }
+}
+
+// grid lines of [start, end] kept with `stride`, the last one is always kept
+fn lod_lines(start: usize, end: usize, stride: usize) -> Vec<usize> {
+    let mut res: Vec<usize> = (start..end).step_by(stride).collect();
+    res.push(end);
+    res
+}
+
+// Splits the terrain into tiles exported at several levels of detail next
+// to `path`: <stem>_<x>_<z>_lod<l>.<ext>, in the format given by the
+// extension of `path`. <stem>.json lists the tiles, their bounds and their
+// files. `texture` is relative to `path`, like the tiles.
+pub fn create_chunked_terrain(
+    res_w: usize,
+    res_h: usize,
+    heightmap: Vec<Vec<f64>>,
+    path: &str,
+    max_height: f64,
+    texture: Option<&str>,
+    opts: &ChunkOpts,
+) -> Result<(), ExportError> {
     let new_height_map = resize_mat(&heightmap, res_w, res_h, max_height);
+    let width = heightmap[0].len() as f64;
+    let height = heightmap.len() as f64;
+    let (nb_slice_w, nb_slice_h) = (res_w - 1, res_h - 1);
+    let tile_size = opts.tile_size.max(1);
+
+    let path = Path::new(path);
+    let invalid = || ExportError::InvalidPath(path.display().to_string());
+    let stem = path
+        .file_stem()
+        .and_then(|x| x.to_str())
+        .ok_or_else(invalid)?;
+    let extension = path.extension().and_then(|x| x.to_str()).unwrap_or("obj");
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let mut tiles = Vec::new();
+
+    for tz in 0..(nb_slice_h + tile_size - 1) / tile_size {
+        for tx in 0..(nb_slice_w + tile_size - 1) / tile_size {
+            let (z0, z1) =
+                (tz * tile_size, ((tz + 1) * tile_size).min(nb_slice_h));
+            let (x0, x1) =
+                (tx * tile_size, ((tx + 1) * tile_size).min(nb_slice_w));
+            let mut files = Vec::new();
+            let (mut y_min, mut y_max) = (f64::MAX, f64::MIN);
+
+            for lod in 0..opts.lods.max(1) {
+                let stride = 1 << lod;
+                let mut tile = Mesh::terrain_tile(
+                    width,
+                    height,
+                    nb_slice_w,
+                    nb_slice_h,
+                    &new_height_map,
+                    &lod_lines(z0, z1, stride),
+                    &lod_lines(x0, x1, stride),
+                );
+
+                if lod == 0 {
+                    for v in &tile.vertex {
+                        y_min = y_min.min(v.y);
+                        y_max = y_max.max(v.y);
+                    }
+                }
+
+                if let Some(depth) = opts.skirt_depth {
+                    tile.add_skirts(|v| v.y - depth);
+                }
+
+                let skirts = opts.skirt_depth.is_some();
+                if let Some(angle) = edge_angle(skirts, opts.hard_edge_angle) {
+                    tile.compute_normals(Some(angle));
+                }
+
+                let name = format!("{stem}_{tx}_{tz}_lod{lod}.{extension}");
+                let tile_path = dir.join(&name);
+                let tile_path = tile_path.to_str().ok_or_else(invalid)?;
+                write_scene(tile_path, &[(Surface::Terrain, &tile)], texture)?;
+                files.push(name);
+            }
+
+            let step_x = width / nb_slice_w as f64;
+            let step_z = height / nb_slice_h as f64;
+            tiles.push(json!({
+                "x": tx,
+                "z": tz,
+                "min": [x0 as f64 * step_x, y_min, z0 as f64 * step_z],
+                "max": [x1 as f64 * step_x, y_max, z1 as f64 * step_z],
+                "lods": files,
+            }));
+        }
+    }
+
+    let manifest = json!({
+        "width": width,
+        "height": height,
+        "tile_size": tile_size,
+        "lods": opts.lods.max(1),
+        "skirt_depth": opts.skirt_depth,
+        "tiles": tiles,
+    });
+
+    let mut file = create_file(dir.join(format!("{stem}.json")))?;
+    serde_json::to_writer_pretty(&mut file, &manifest)?;
+    file.flush()?;
+    Ok(())
+}
+
+fn write_scene(
+    path: &str,
+    scene: &[(Surface, &Mesh)],
+    texture: Option<&str>,
+) -> Result<(), ExportError> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|x| x.to_str())
+        .map(|x| x.to_ascii_lowercase());
+
+    let png_path = texture_path(path, texture)?;
+
+    match extension.as_deref() {
+        Some("glb") => {
+            let png = match png_path {
+                Some(png_path) => Some(fs::read(png_path)?),
+                None => None,
+            };
+            let texture =
+                png.as_deref().map_or(Texture::None, Texture::Embedded);
+            Mesh::write_glb_scene(create_file(path)?, scene, &texture)
+        }
+        Some("gltf") => {
+            let texture = texture.map_or(Texture::None, Texture::Referenced);
+            Mesh::to_gltf_scene(path, scene, &texture)
+        }
+        _ => Mesh::to_obj_scene(path, scene, texture),
+    }
+}
+
+// watertight solid for 3D printing, written as a binary STL. Heights are
+// multiplied by `exaggeration` and the base is `base_thickness` thick, both
+// in the exported height unit
+pub fn create_printable_terrain(
+    res_w: usize,
+    res_h: usize,
+    heightmap: Vec<Vec<f64>>,
+    path: &str,
+    max_height: f64,
+    exaggeration: f64,
+    base_thickness: f64,
+) -> Result<(), ExportError> {
+    let new_height_map =
+        resize_mat(&heightmap, res_w, res_h, max_height * exaggeration);
     let mesh = Mesh::terrain(
         heightmap[0].len() as f64,
         heightmap.len() as f64,
@@ -15,7 +334,7 @@ pub fn create_3d_terrain(
         res_h - 1,
         new_height_map,
     );
-    mesh.to_obj(path)
+    mesh.solid(base_thickness).to_stl(path, true)
 }
 
 fn resize_mat(
@@ -24,15 +343,25 @@ fn resize_mat(
     new_size_y: usize,
     max_height: f64,
 ) -> Vec<Vec<f64>> {
+    resample(mat, new_size_x, new_size_y)
+        .into_iter()
+        .map(|row| row.into_iter().map(|x| x * max_height).collect())
+        .collect()
+}
+
+fn resample<T: Copy + Default>(
+    mat: &Vec<Vec<T>>,
+    new_size_x: usize,
+    new_size_y: usize,
+) -> Vec<Vec<T>> {
     let scaling_factor_x: f64 = new_size_x as f64 / mat.len() as f64;
     let scaling_factor_y: f64 = new_size_y as f64 / mat[0].len() as f64;
-    let mut resized_mat = vec![vec![0.0; new_size_y]; new_size_x];
+    let mut resized_mat = vec![vec![T::default(); new_size_y]; new_size_x];
     for i in 0..new_size_x {
         for j in 0..new_size_y {
             let new_i = i as f64 / scaling_factor_x;
             let new_j = j as f64 / scaling_factor_y;
-            resized_mat[i][j] =
-                mat[new_j as usize][new_i as usize] * max_height;
+            resized_mat[i][j] = mat[new_j as usize][new_i as usize];
         }
     }
     resized_mat
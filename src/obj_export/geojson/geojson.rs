@@ -0,0 +1,81 @@
+use crate::display::settlements::{Road, Settlement, SettlementKind};
+use crate::obj_export::writer::writer::{create_file, ExportError};
+
+use serde_json::{json, Value};
+use std::io::prelude::*;
+
+// Settlements as points and roads as lines, in a GeoJSON feature
+// collection. The coordinates are in cells of the map, x to the right and y
+// downwards like the exported image, whose size is given along.
+pub fn geojson_document(
+    settlements: &[Settlement],
+    roads: &[Road],
+    (width, height): (usize, usize),
+) -> Value {
+    let points = settlements.iter().enumerate().map(|(id, x)| {
+        json!({
+            "type": "Feature",
+            "id": id,
+            "geometry": {
+                "type": "Point",
+                "coordinates": [x.pos.0, x.pos.1],
+            },
+            "properties": {
+                "kind": match x.kind {
+                    SettlementKind::City => "city",
+                    SettlementKind::Village => "village",
+                },
+                "name": x.name,
+            },
+        })
+    });
+
+    let lines = roads.iter().enumerate().map(|(id, x)| {
+        json!({
+            "type": "Feature",
+            "id": settlements.len() + id,
+            "geometry": {
+                "type": "LineString",
+                "coordinates": x.path
+                    .iter()
+                    .map(|(i, j)| [*i, *j])
+                    .collect::<Vec<_>>(),
+            },
+            "properties": {
+                "kind": "road",
+                // ids of the settlements it joins
+                "from": x.from,
+                "to": x.to,
+            },
+        })
+    });
+
+    json!({
+        "type": "FeatureCollection",
+        "width": width,
+        "height": height,
+        "features": points.chain(lines).collect::<Vec<_>>(),
+    })
+}
+
+pub fn write_geojson<W: Write>(
+    mut out: W,
+    settlements: &[Settlement],
+    roads: &[Road],
+    size: (usize, usize),
+) -> Result<(), ExportError> {
+    let document = geojson_document(settlements, roads, size);
+
+    serde_json::to_writer_pretty(&mut out, &document)?;
+    out.flush()?;
+    Ok(())
+}
+
+pub fn export_geojson(
+    path: &str,
+    settlements: &[Settlement],
+    roads: &[Road],
+    size: (usize, usize),
+) -> Result<(), ExportError> {
+    write_geojson(create_file(path)?, settlements, roads, size)
+}
@@ -0,0 +1,315 @@
+use crate::obj_export::mesh::mesh::Mesh;
+use crate::obj_export::writer::writer::{
+    create_file, sibling, ExportError, Surface,
+};
+
+use serde_json::{json, Value};
+use std::io::prelude::*;
+
+const GLB_MAGIC: u32 = 0x46546C67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4E4F534A;
+const CHUNK_BIN: u32 = 0x004E4942;
+
+const COMPONENT_FLOAT: u32 = 5126;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FILTER_LINEAR: u32 = 9729;
+
+pub enum Texture<'a> {
+    None,
+    // written as an uri, relative to the exported file
+    Referenced(&'a str),
+    // png bytes stored in the binary buffer
+    Embedded(&'a [u8]),
+}
+
+struct Builder {
+    bin: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            bin: Vec::new(),
+            buffer_views: Vec::new(),
+            accessors: Vec::new(),
+        }
+    }
+
+    fn push_view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        while self.bin.len() % 4 != 0 {
+            self.bin.push(0);
+        }
+
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.bin.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+
+        self.bin.extend_from_slice(bytes);
+        self.buffer_views.push(view);
+        self.buffer_views.len() - 1
+    }
+
+    fn push_floats(&mut self, values: &[[f32; 3]], kind: &str) -> usize {
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.iter().flat_map(|x| x.to_le_bytes()))
+            .collect();
+        let view = self.push_view(&bytes, Some(TARGET_ARRAY_BUFFER));
+
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": COMPONENT_FLOAT,
+            "count": values.len(),
+            "type": kind,
+        });
+
+        if kind == "VEC3" {
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for v in values {
+                for k in 0..3 {
+                    min[k] = min[k].min(v[k]);
+                    max[k] = max[k].max(v[k]);
+                }
+            }
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn push_uvs(&mut self, values: &[[f32; 2]]) -> usize {
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.iter().flat_map(|x| x.to_le_bytes()))
+            .collect();
+        let view = self.push_view(&bytes, Some(TARGET_ARRAY_BUFFER));
+
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": COMPONENT_FLOAT,
+            "count": values.len(),
+            "type": "VEC2",
+        }));
+        self.accessors.len() - 1
+    }
+
+    fn push_indices(&mut self, indices: &[(usize, usize, usize)]) -> usize {
+        let bytes: Vec<u8> = indices
+            .iter()
+            .flat_map(|&(a, b, c)| [a as u32, b as u32, c as u32])
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let view = self.push_view(&bytes, Some(TARGET_ELEMENT_ARRAY_BUFFER));
+
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": COMPONENT_UNSIGNED_INT,
+            "count": indices.len() * 3,
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+// one node and one mesh per surface, each with its own material
+fn gltf_document(
+    scene: &[(Surface, &Mesh)],
+    buffer_uri: Option<&str>,
+    texture: &Texture,
+) -> (Value, Vec<u8>) {
+    let mut builder = Builder::new();
+    let mut meshes = Vec::new();
+    let mut materials = Vec::new();
+
+    let image = match texture {
+        Texture::None => None,
+        Texture::Referenced(uri) => Some(json!({ "uri": uri })),
+        Texture::Embedded(png) => {
+            let view = builder.push_view(png, None);
+            Some(json!({ "bufferView": view, "mimeType": "image/png" }))
+        }
+    };
+
+    for (surface, mesh) in scene {
+        let positions: Vec<[f32; 3]> = mesh
+            .vertex
+            .iter()
+            .map(|v| [v.x as f32, v.y as f32, v.z as f32])
+            .collect();
+        let normals: Vec<[f32; 3]> = mesh
+            .normals
+            .iter()
+            .map(|v| [v.x as f32, v.y as f32, v.z as f32])
+            .collect();
+        let uvs: Vec<[f32; 2]> = mesh
+            .vertex
+            .iter()
+            .map(|v| {
+                // glTF puts the origin at the top left of the image
+                let (u, v) = mesh.uv(v);
+                [u as f32, (1.0 - v) as f32]
+            })
+            .collect();
+
+        let position = builder.push_floats(&positions, "VEC3");
+        let normal = builder.push_floats(&normals, "VEC3");
+        let uv = builder.push_uvs(&uvs);
+        let indices = builder.push_indices(&mesh.indices);
+
+        meshes.push(json!({
+            "name": surface.name(),
+            "primitives": [{
+                "attributes": {
+                    "POSITION": position,
+                    "NORMAL": normal,
+                    "TEXCOORD_0": uv,
+                },
+                "indices": indices,
+                "material": materials.len(),
+            }],
+        }));
+
+        let color = surface.color();
+        let mut material = json!({
+            "name": surface.name(),
+            "pbrMetallicRoughness": {
+                "baseColorFactor": color,
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+            "doubleSided": false,
+        });
+        if color[3] < 1.0 {
+            material["alphaMode"] = json!("BLEND");
+        }
+        if surface.textured() && image.is_some() {
+            material["pbrMetallicRoughness"]["baseColorTexture"] =
+                json!({ "index": 0 });
+        }
+        materials.push(material);
+    }
+
+    let mut buffer = json!({ "byteLength": builder.bin.len() });
+    if let Some(uri) = buffer_uri {
+        buffer["uri"] = json!(uri);
+    }
+
+    let nodes: Vec<Value> = scene
+        .iter()
+        .enumerate()
+        .map(|(k, (surface, _))| json!({ "mesh": k, "name": surface.name() }))
+        .collect();
+
+    let mut document = json!({
+        "asset": { "version": "2.0", "generator": "pimp_my_map" },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": materials,
+        "buffers": [buffer],
+        "bufferViews": builder.buffer_views,
+        "accessors": builder.accessors,
+    });
+
+    if let Some(image) = image {
+        document["images"] = json!([image]);
+        document["samplers"] = json!([{
+            "magFilter": FILTER_LINEAR,
+            "minFilter": FILTER_LINEAR,
+        }]);
+        document["textures"] = json!([{ "sampler": 0, "source": 0 }]);
+    }
+
+    (document, builder.bin)
+}
+
+impl Mesh {
+    pub fn to_gltf(
+        &self,
+        path: &str,
+        texture: &Texture,
+    ) -> Result<(), ExportError> {
+        Mesh::to_gltf_scene(path, &[(Surface::Terrain, self)], texture)
+    }
+
+    pub fn write_glb<W: Write>(
+        &self,
+        out: W,
+        texture: &Texture,
+    ) -> Result<(), ExportError> {
+        Mesh::write_glb_scene(out, &[(Surface::Terrain, self)], texture)
+    }
+
+    pub fn to_glb(
+        &self,
+        path: &str,
+        texture: &Texture,
+    ) -> Result<(), ExportError> {
+        self.write_glb(create_file(path)?, texture)
+    }
+
+    // writes `path` (.gltf) and its buffer next to it (.bin)
+    pub fn to_gltf_scene(
+        path: &str,
+        scene: &[(Surface, &Mesh)],
+        texture: &Texture,
+    ) -> Result<(), ExportError> {
+        let (bin_path, bin_name) = sibling(path, "bin")?;
+        let (document, bin) = gltf_document(scene, Some(&bin_name), texture);
+
+        let mut bin_file = create_file(bin_path)?;
+        bin_file.write_all(&bin)?;
+        bin_file.flush()?;
+
+        let mut file = create_file(path)?;
+        serde_json::to_writer(&mut file, &document)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    // single binary file: header, json chunk and binary chunk
+    pub fn write_glb_scene<W: Write>(
+        mut out: W,
+        scene: &[(Surface, &Mesh)],
+        texture: &Texture,
+    ) -> Result<(), ExportError> {
+        let (document, mut bin) = gltf_document(scene, None, texture);
+        let mut json = serde_json::to_vec(&document)?;
+
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+        while bin.len() % 4 != 0 {
+            bin.push(0);
+        }
+
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+
+        out.write_all(&GLB_MAGIC.to_le_bytes())?;
+        out.write_all(&GLB_VERSION.to_le_bytes())?;
+        out.write_all(&(length as u32).to_le_bytes())?;
+        out.write_all(&(json.len() as u32).to_le_bytes())?;
+        out.write_all(&CHUNK_JSON.to_le_bytes())?;
+        out.write_all(&json)?;
+        out.write_all(&(bin.len() as u32).to_le_bytes())?;
+        out.write_all(&CHUNK_BIN.to_le_bytes())?;
+        out.write_all(&bin)?;
+
+        out.flush()?;
+        Ok(())
+    }
+}
@@ -20,7 +20,7 @@ fn main() {
  //   dbg!(&mesh);
        
    let start_export = Instant::now();
-   mesh.to_obj("./test.obj").unwrap();
+   mesh.to_obj("./test.obj", None).unwrap();
    println!("EXPORT IN OBJ TIME: {:?}", start_export.elapsed());
    println!("TOTAL DURATION: {:?}", start.elapsed());
 }
@@ -1,8 +1,12 @@
 use crate::obj_export::delaunay::delaunay::conquere;
 use crate::obj_export::graph::graph::Graph;
+use crate::obj_export::simplify::simplify::simplify_heightmap;
 use crate::obj_export::vector3::vector3::Vector3;
+use crate::obj_export::writer::writer::{
+    create_file, sibling, ExportError, Surface,
+};
 
-use std::fs::{self, File};
+use std::collections::{HashMap, HashSet};
 use std::io::prelude::*;
 
 #[derive(Debug, Clone)]
@@ -31,14 +35,6 @@ impl Mesh {
         nb_slice_w: usize,
         nb_slice_h: usize,
     ) -> Self {
-        let mut cache_file = match File::create("./.tmp_obj_file") {
-            Ok(f) => Some(f),
-            Err(e) => {
-                println!("Error while creating cache file: {e}");
-                None
-            }
-        };
-
         let step_x = width / nb_slice_w as f64;
         let step_z = height / nb_slice_h as f64;
         let mut vertex = Vec::new();
@@ -46,30 +42,20 @@ impl Mesh {
         let mut indice = 0;
         let mut points = Vec::new();
 
-        cache_file = write_in_cache(cache_file, "# Vertices and Textures\n");
-
         for _ in 0..=nb_slice_h {
             let mut x = 0.0;
             for _ in 0..=nb_slice_w {
                 vertex.push(Vector3::from(x, 0.0, z, indice));
                 points.push(indice);
-                cache_file = write_in_cache(
-                    cache_file,
-                    &format!("v {} {} {}\n", x, 0.0, z),
-                );
-                cache_file =
-                    write_in_cache(cache_file, &format!("vt {} {}\n", z, x));
                 indice += 1;
                 x += step_x;
             }
             z += step_z;
         }
         vertex[0].y = 100.0;
-        cache_file = write_in_cache(cache_file, "# triangles and normals\n");
         let mut graph = Graph::new(points.len(), vertex.clone());
         conquere(&points, nb_slice_w + 1, nb_slice_h + 1, &mut graph);
         let mut indices = Vec::new();
-        let mut normals = Vec::new();
         for i in 0..graph.order {
             for j in 0..graph.adjlists[i].len() {
                 for k in j + 1..graph.adjlists[i].len() {
@@ -77,57 +63,25 @@ impl Mesh {
                     let elt_k = graph.adjlists[i][k];
 
                     if graph.adjlists[elt_j].contains(&elt_k) {
-                        indices.push((
-                            i,
-                            graph.adjlists[i][j],
-                            graph.adjlists[i][k],
-                        ));
-                        let s1 = vertex[i].clone();
-                        let s2 = &vertex[graph.adjlists[i][j]];
-                        let mut vec_normal = s1.vec_product(s2);
-                        vec_normal.normalize();
-                        cache_file = write_in_cache(
-                            cache_file,
-                            &format!(
-                                "vn {} {} {}\n",
-                                vec_normal.x, vec_normal.y, vec_normal.z
-                            ),
+                        // the graph has no orientation, face up
+                        let normal = face_normal(
+                            &vertex[i],
+                            &vertex[elt_j],
+                            &vertex[elt_k],
                         );
-                        normals.push(if vec_normal.is_zero() {
-                            Vector3::from(0.0, 1.0, 0.0, 0)
+                        if normal.y < 0.0 {
+                            indices.push((i, elt_k, elt_j));
                         } else {
-                            vec_normal
-                        });
+                            indices.push((i, elt_j, elt_k));
+                        }
                         graph.adjlists[elt_j].retain(|x| *x != i);
                         graph.adjlists[elt_k].retain(|x| *x != i);
-                        cache_file = write_in_cache(
-                            cache_file,
-                            &format!(
-                                "f {}/{}/{} {}/{}/{} {}/{}/{}\n",
-                                i + 1,
-                                i + 1,
-                                normals.len(),
-                                graph.adjlists[i][j] + 1,
-                                graph.adjlists[i][j] + 1,
-                                normals.len(),
-                                graph.adjlists[i][k] + 1,
-                                graph.adjlists[i][k] + 1,
-                                normals.len(),
-                            ),
-                        );
                     }
                 }
             }
         }
 
-        let mesh = Mesh {
-            vertex,
-            normals,
-            indices,
-            x_max: width,
-            z_max: height,
-        };
-        mesh
+        Mesh::from_triangles(vertex, indices, width, height, None)
     }
 
     pub fn terrain(
@@ -137,216 +91,486 @@ impl Mesh {
         nb_slice_h: usize,
         heightmap: Vec<Vec<f64>>,
     ) -> Self {
-        let mut cache_file = match File::create("./.tmp_obj_file") {
-            Ok(f) => Some(f),
-            Err(e) => {
-                println!("Error while creating cache file: {e}");
-                None
+        Mesh::grid_terrain(
+            width, height, nb_slice_w, nb_slice_h, heightmap, true, None,
+        )
+    }
+
+    // The points of a terrain are always on a regular grid, so every quad is
+    // split in two triangles directly instead of running the Delaunay
+    // triangulation. With `flip_diagonals`, each quad is cut along the
+    // diagonal with the smallest height difference, which follows ridges and
+    // valleys instead of cutting through them.
+    pub fn grid_terrain(
+        width: f64,
+        height: f64,
+        nb_slice_w: usize,
+        nb_slice_h: usize,
+        heightmap: Vec<Vec<f64>>,
+        flip_diagonals: bool,
+        hard_edge_angle: Option<f64>,
+    ) -> Self {
+        let step_x = width / nb_slice_w as f64;
+        let step_z = height / nb_slice_h as f64;
+        let mut vertex =
+            Vec::with_capacity((nb_slice_w + 1) * (nb_slice_h + 1));
+
+        for i in 0..=nb_slice_h {
+            for j in 0..=nb_slice_w {
+                vertex.push(Vector3::from(
+                    j as f64 * step_x,
+                    heightmap[i][j],
+                    i as f64 * step_z,
+                    vertex.len(),
+                ));
             }
-        };
+        }
+
+        let indices =
+            grid_triangles(nb_slice_w, nb_slice_h, &vertex, flip_diagonals);
+
+        Mesh::from_triangles(vertex, indices, width, height, hard_edge_angle)
+    }
 
+    // Part of a terrain made of the grid lines `rows` and `cols` of the
+    // heightmap, which don't have to be evenly spaced. Positions and
+    // texture coordinates are the ones of the whole terrain.
+    pub fn terrain_tile(
+        width: f64,
+        height: f64,
+        nb_slice_w: usize,
+        nb_slice_h: usize,
+        heightmap: &Vec<Vec<f64>>,
+        rows: &[usize],
+        cols: &[usize],
+    ) -> Self {
         let step_x = width / nb_slice_w as f64;
         let step_z = height / nb_slice_h as f64;
-        let mut vertex = Vec::new();
-        let mut z = 0.0;
-        let mut indice = 0;
-        let mut points = Vec::new();
+        let mut vertex = Vec::with_capacity(rows.len() * cols.len());
 
-        cache_file = write_in_cache(cache_file, "# Vertices and Textures\n");
+        for &i in rows {
+            for &j in cols {
+                vertex.push(Vector3::from(
+                    j as f64 * step_x,
+                    heightmap[i][j],
+                    i as f64 * step_z,
+                    vertex.len(),
+                ));
+            }
+        }
+
+        let indices =
+            grid_triangles(cols.len() - 1, rows.len() - 1, &vertex, true);
+
+        Mesh::from_triangles(vertex, indices, width, height, None)
+    }
+
+    // Keeps only the grid points needed for the surface to stay within
+    // `tolerance` (in heightmap units) of every cell, flat areas end up with
+    // a handful of large triangles.
+    pub fn simplified_terrain(
+        width: f64,
+        height: f64,
+        nb_slice_w: usize,
+        nb_slice_h: usize,
+        heightmap: Vec<Vec<f64>>,
+        tolerance: f64,
+        hard_edge_angle: Option<f64>,
+    ) -> Self {
+        let step_x = width / nb_slice_w as f64;
+        let step_z = height / nb_slice_h as f64;
+
+        let (points, indices) = simplify_heightmap(
+            &heightmap,
+            nb_slice_w + 1,
+            nb_slice_h + 1,
+            tolerance,
+            usize::MAX,
+        );
+
+        let vertex = points
+            .iter()
+            .enumerate()
+            .map(|(indice, &(i, j))| {
+                Vector3::from(
+                    j as f64 * step_x,
+                    heightmap[i][j],
+                    i as f64 * step_z,
+                    indice,
+                )
+            })
+            .collect();
+
+        Mesh::from_triangles(vertex, indices, width, height, hard_edge_angle)
+    }
+
+    // Flat surface at `level` over the cells where `water` is set, on the
+    // same grid as `terrain`. Triangles touching a water cell are kept so
+    // that the coast ends under the terrain instead of leaving a gap.
+    pub fn water(
+        width: f64,
+        height: f64,
+        nb_slice_w: usize,
+        nb_slice_h: usize,
+        water: &Vec<Vec<bool>>,
+        level: f64,
+    ) -> Self {
+        let step_x = width / nb_slice_w as f64;
+        let step_z = height / nb_slice_h as f64;
+        let mut grid = Vec::with_capacity((nb_slice_w + 1) * (nb_slice_h + 1));
 
         for i in 0..=nb_slice_h {
-            let mut x = 0.0;
             for j in 0..=nb_slice_w {
-                vertex.push(Vector3::from(x, heightmap[i][j], z, indice));
-                points.push(indice);
-                cache_file = write_in_cache(
-                    cache_file,
-                    &format!("v {} {} {}\n", x, heightmap[j][i], z),
-                );
-                cache_file = write_in_cache(
-                    cache_file,
-                    &format!("vt {} {}\n", 1.0 - x / width, z / height),
-                );
-                indice += 1;
-                x += step_x;
+                grid.push(Vector3::from(
+                    j as f64 * step_x,
+                    level,
+                    i as f64 * step_z,
+                    grid.len(),
+                ));
             }
-            z += step_z;
         }
 
-        let mut graph = Graph::new(points.len(), vertex.clone());
-        conquere(&points, nb_slice_w + 1, nb_slice_h + 1, &mut graph);
-        let mut indices = Vec::new();
-        let mut normals = Vec::new();
-        cache_file = write_in_cache(cache_file, "# triangles and normals\n");
-        for i in 0..graph.order {
-            for j in 0..graph.adjlists[i].len() {
-                for k in j + 1..graph.adjlists[i].len() {
-                    let elt_j = graph.adjlists[i][j];
-                    let elt_k = graph.adjlists[i][k];
+        let row = nb_slice_w + 1;
+        let is_water = |v: usize| water[v / row][v % row];
 
-                    if graph.adjlists[elt_j].contains(&elt_k) {
-                        indices.push((
-                            i,
-                            graph.adjlists[i][j],
-                            graph.adjlists[i][k],
-                        ));
-                        let s1 = vertex[i].clone();
-                        let s2 = &vertex[graph.adjlists[i][j]];
-                        let mut vec_normal = s1.vec_product(s2);
-                        vec_normal.normalize();
-                        cache_file = write_in_cache(
-                            cache_file,
-                            &format!(
-                                "vn {} {} {}\n",
-                                vec_normal.x, vec_normal.y, vec_normal.z
-                            ),
-                        );
-                        normals.push(if vec_normal.is_zero() {
-                            Vector3::from(0.0, 1.0, 0.0, 0)
-                        } else {
-                            vec_normal
-                        });
-                        graph.adjlists[elt_j].retain(|x| *x != i);
-                        graph.adjlists[elt_k].retain(|x| *x != i);
-                        cache_file = write_in_cache(
-                            cache_file,
-                            &format!(
-                                "f {}/{}/{} {}/{}/{} {}/{}/{}\n",
-                                i + 1,
-                                i + 1,
-                                normals.len(),
-                                graph.adjlists[i][j] + 1,
-                                graph.adjlists[i][j] + 1,
-                                normals.len(),
-                                graph.adjlists[i][k] + 1,
-                                graph.adjlists[i][k] + 1,
-                                normals.len(),
-                            ),
-                        );
-                    }
-                }
-            }
+        // only the vertices of the kept triangles are exported
+        let mut vertex = Vec::new();
+        let mut kept = HashMap::new();
+        let indices = grid_triangles(nb_slice_w, nb_slice_h, &grid, false)
+            .into_iter()
+            .filter(|&(a, b, c)| is_water(a) || is_water(b) || is_water(c))
+            .map(|(a, b, c)| {
+                let [a, b, c] = [a, b, c].map(|v| {
+                    *kept.entry(v).or_insert_with(|| {
+                        let mut new_vertex = grid[v].clone();
+                        new_vertex.indice = vertex.len();
+                        vertex.push(new_vertex);
+                        vertex.len() - 1
+                    })
+                });
+                (a, b, c)
+            })
+            .collect();
+
+        Mesh::from_triangles(vertex, indices, width, height, None)
+    }
+
+    // same surface with the vertices split for hard edges merged back
+    pub fn welded(&self) -> Mesh {
+        let mut vertex: Vec<Vector3> = Vec::new();
+        let mut welded: HashMap<[u64; 3], usize> = HashMap::new();
+        let remap: Vec<usize> = self
+            .vertex
+            .iter()
+            .map(|v| {
+                let key = [v.x.to_bits(), v.y.to_bits(), v.z.to_bits()];
+                *welded.entry(key).or_insert_with(|| {
+                    vertex.push(Vector3::from(v.x, v.y, v.z, vertex.len()));
+                    vertex.len() - 1
+                })
+            })
+            .collect();
+
+        let mut res = Mesh {
+            vertex,
+            normals: Vec::new(),
+            indices: self
+                .indices
+                .iter()
+                .map(|&(a, b, c)| (remap[a], remap[b], remap[c]))
+                .collect(),
+            x_max: self.x_max,
+            z_max: self.z_max,
+        };
+        res.compute_normals(None);
+        res
+    }
+
+    // Vertical walls under every boundary edge of a welded surface, down to
+    // `bottom(vertex)`. Returns the lower edges of the walls, oriented like
+    // the boundary. Normals have to be computed again afterwards.
+    pub fn add_skirts(
+        &mut self,
+        bottom: impl Fn(&Vector3) -> f64,
+    ) -> Vec<(usize, usize)> {
+        let edges: HashSet<(usize, usize)> = self
+            .indices
+            .iter()
+            .flat_map(|&(a, b, c)| [(a, b), (b, c), (c, a)])
+            .collect();
+        let boundary: Vec<(usize, usize)> = self
+            .indices
+            .iter()
+            .flat_map(|&(a, b, c)| [(a, b), (b, c), (c, a)])
+            .filter(|&(a, b)| !edges.contains(&(b, a)))
+            .collect();
+
+        let mut lower: HashMap<usize, usize> = HashMap::new();
+        let mut res = Vec::with_capacity(boundary.len());
+
+        for (a, b) in boundary {
+            let [a2, b2] = [a, b].map(|i| {
+                *lower.entry(i).or_insert_with(|| {
+                    let v = &self.vertex[i];
+                    let new_vertex =
+                        Vector3::from(v.x, bottom(v), v.z, self.vertex.len());
+                    self.vertex.push(new_vertex);
+                    self.vertex.len() - 1
+                })
+            });
+
+            self.indices.push((b, a, a2));
+            self.indices.push((b, a2, b2));
+            res.push((a2, b2));
         }
 
-        let mesh = Mesh {
+        res
+    }
+
+    fn from_triangles(
+        vertex: Vec<Vector3>,
+        indices: Vec<(usize, usize, usize)>,
+        width: f64,
+        height: f64,
+        hard_edge_angle: Option<f64>,
+    ) -> Self {
+        let mut mesh = Mesh {
             vertex,
-            normals,
+            normals: Vec::new(),
             indices,
             x_max: width,
             z_max: height,
         };
+        mesh.compute_normals(hard_edge_angle);
+
         mesh
     }
 
-    pub fn to_obj(&self, path: &str) -> Result<(), String> {
-        if fs::metadata("./.tmp_obj_file").is_ok() {
-            match fs::rename("./.tmp_obj_file", path) {
-                Ok(_) => return Ok(()),
-                Err(_) => (),
-            };
-        }
+    // Area weighted vertex normals, one per vertex. With `hard_edge_angle`
+    // (in degrees) faces meeting at a sharper angle don't share their normal:
+    // the vertex is duplicated for each group of faces.
+    pub fn compute_normals(&mut self, hard_edge_angle: Option<f64>) {
+        // cross products, their length is twice the area of the face
+        let faces: Vec<Vector3> = self
+            .indices
+            .iter()
+            .map(|&(a, b, c)| {
+                let (a, b, c) =
+                    (&self.vertex[a], &self.vertex[b], &self.vertex[c]);
+                (b - a).vec_product(&(c - a))
+            })
+            .collect();
 
-        let mut file = match File::create(path) {
-            Ok(f) => f,
-            Err(e) => {
-                return Err(format!("Error on file creation: {e}"));
+        let Some(angle) = hard_edge_angle else {
+            let mut normals = vec![Vector3::new(); self.vertex.len()];
+            for (&(a, b, c), face) in self.indices.iter().zip(faces.iter()) {
+                normals[a] += face;
+                normals[b] += face;
+                normals[c] += face;
             }
+
+            self.normals = normals.into_iter().map(unit_or_up).collect();
+            return;
         };
 
-        match file.write_all(b"# Vertices\n") {
-            Err(e) => {
-                return Err(format!("Error while writing in file: {e}"));
-            }
-            _ => (),
-        }
+        let units: Vec<Vector3> =
+            faces.iter().map(|x| unit_or_up(x.clone())).collect();
+        let threshold = angle.to_radians().cos();
 
-        for v in &self.vertex {
-            let line = format!("v {} {} {}\n", v.x, v.y, v.z);
-            match file.write_all(line.as_bytes()) {
-                Err(e) => {
-                    return Err(format!("Error while writing in file: {e}"));
-                }
-                _ => (),
-            }
+        let mut incident = vec![Vec::new(); self.vertex.len()];
+        for (f, &(a, b, c)) in self.indices.iter().enumerate() {
+            incident[a].push(f);
+            incident[b].push(f);
+            incident[c].push(f);
         }
 
-        match file.write_all(b"# Normals\n") {
-            Err(e) => {
-                return Err(format!("Error while writing in file: {e}"));
-            }
-            _ => (),
+        let mut vertex = Vec::new();
+        let mut normals = Vec::new();
+        let mut groups: HashMap<(usize, Vec<usize>), usize> = HashMap::new();
+        let mut indices = Vec::with_capacity(self.indices.len());
+
+        for (f, &(a, b, c)) in self.indices.iter().enumerate() {
+            let mut corner = |v: usize| {
+                let group: Vec<usize> = incident[v]
+                    .iter()
+                    .copied()
+                    .filter(|&g| units[f].dot(&units[g]) >= threshold)
+                    .collect();
+
+                *groups.entry((v, group)).or_insert_with_key(|(_, group)| {
+                    let mut normal = Vector3::new();
+                    group.iter().for_each(|&g| normal += &faces[g]);
+
+                    let mut new_vertex = self.vertex[v].clone();
+                    new_vertex.indice = vertex.len();
+                    vertex.push(new_vertex);
+                    normals.push(unit_or_up(normal));
+                    vertex.len() - 1
+                })
+            };
+
+            indices.push((corner(a), corner(b), corner(c)));
         }
 
-        for vn in &self.normals {
-            let line = format!("vn {} {} {}\n", vn.x, vn.y, vn.z);
-            match file.write_all(line.as_bytes()) {
-                Err(e) => {
-                    return Err(format!("Error while writing in file: {e}"));
-                }
-                _ => (),
-            }
+        self.vertex = vertex;
+        self.normals = normals;
+        self.indices = indices;
+    }
+
+    // OBJ texture coordinates of a vertex (origin at the bottom left of the
+    // image), the texture covers the whole terrain seen from above
+    pub fn uv(&self, v: &Vector3) -> (f64, f64) {
+        (v.x / self.x_max, 1.0 - v.z / self.z_max)
+    }
+
+    // OBJ text of the mesh, using the material library `mtllib` when given.
+    // `out` is buffered, it can be a file as well as memory or a socket.
+    pub fn write_obj<W: Write>(
+        &self,
+        out: W,
+        mtllib: Option<&str>,
+    ) -> Result<(), ExportError> {
+        Mesh::write_obj_scene(out, &[(Surface::Terrain, self)], mtllib)
+    }
+
+    // several meshes in one file, each one is a named object with the
+    // material of its surface
+    pub fn write_obj_scene<W: Write>(
+        mut out: W,
+        scene: &[(Surface, &Mesh)],
+        mtllib: Option<&str>,
+    ) -> Result<(), ExportError> {
+        // OBJ indices are global to the file and start at 1
+        let mut offset = 1;
+
+        if let Some(mtllib) = mtllib {
+            writeln!(out, "mtllib {mtllib}")?;
         }
 
-        match file.write_all(b"# Textures\n") {
-            Err(e) => {
-                return Err(format!("Error while writing in file: {e}"));
+        for (surface, mesh) in scene {
+            writeln!(out, "o {}", surface.name())?;
+            if mtllib.is_some() {
+                writeln!(out, "usemtl {}", surface.name())?;
             }
-            _ => (),
-        }
 
-        for vt in &self.vertex {
-            let line = format!("vt {} {}\n", vt.x, vt.z);
-            match file.write_all(line.as_bytes()) {
-                Err(e) => {
-                    return Err(format!("Error while writing in file: {e}"));
-                }
-                _ => (),
+            writeln!(out, "# Vertices, Textures and Normals")?;
+            for (v, n) in mesh.vertex.iter().zip(mesh.normals.iter()) {
+                let (tu, tv) = mesh.uv(v);
+                write!(
+                    out,
+                    "v {} {} {}\nvt {} {}\nvn {} {} {}\n",
+                    v.x, v.y, v.z, tu, tv, n.x, n.y, n.z
+                )?;
             }
-        }
 
-        match file.write_all(b"# Triangles\n") {
-            Err(e) => {
-                return Err(format!("Error while writing in file: {e}"));
+            writeln!(out, "# Triangles")?;
+            for i in &mesh.indices {
+                writeln!(
+                    out,
+                    "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}",
+                    i.0 + offset,
+                    i.1 + offset,
+                    i.2 + offset,
+                )?;
             }
-            _ => (),
+
+            offset += mesh.vertex.len();
         }
 
-        let mut j = 1;
-        for i in &self.indices {
-            let line = format!(
-                "f {}/{}/{} {}/{}/{} {}/{}/{}\n",
-                i.0 + 1,
-                j,
-                j,
-                i.1 + 1,
-                j,
-                j,
-                i.2 + 1,
-                j,
-                j
-            );
-            j += 1;
-            match file.write_all(line.as_bytes()) {
-                Err(e) => {
-                    return Err(format!("Error while writing in file: {e}"));
-                }
-                _ => (),
+        out.flush()?;
+        Ok(())
+    }
+
+    // material library of `surfaces`, the image `texture` is the diffuse map
+    // of the textured ones
+    pub fn write_mtl<W: Write>(
+        mut out: W,
+        surfaces: &[Surface],
+        texture: Option<&str>,
+    ) -> Result<(), ExportError> {
+        for surface in surfaces {
+            let [r, g, b, a] = surface.color();
+            write!(
+                out,
+                "newmtl {}\nKa {r} {g} {b}\nKd {r} {g} {b}\nKs 0 0 0\nd {a}\nillum 1\n",
+                surface.name()
+            )?;
+            if let (true, Some(texture)) = (surface.textured(), texture) {
+                writeln!(out, "map_Kd {texture}")?;
             }
         }
 
+        out.flush()?;
         Ok(())
     }
+
+    // Writes `path` and its material next to it (same name, .mtl). The
+    // image at `texture`, relative to `path`, is used as the diffuse map.
+    pub fn to_obj(
+        &self,
+        path: &str,
+        texture: Option<&str>,
+    ) -> Result<(), ExportError> {
+        Mesh::to_obj_scene(path, &[(Surface::Terrain, self)], texture)
+    }
+
+    pub fn to_obj_scene(
+        path: &str,
+        scene: &[(Surface, &Mesh)],
+        texture: Option<&str>,
+    ) -> Result<(), ExportError> {
+        let (mtl_path, mtl_name) = sibling(path, "mtl")?;
+        let surfaces: Vec<Surface> = scene.iter().map(|x| x.0).collect();
+
+        Mesh::write_mtl(create_file(mtl_path)?, &surfaces, texture)?;
+        Mesh::write_obj_scene(create_file(path)?, scene, Some(&mtl_name))
+    }
 }
 
-fn write_in_cache(cache_file: Option<File>, msg: &str) -> Option<File> {
-    match cache_file {
-        Some(mut f) => match f.write_all(msg.as_bytes()) {
-            Ok(_) => Some(f),
-            Err(e) => {
-                println!("Error while writing in the cache: {e}");
-                None
+// Two counter-clockwise triangles (seen from +y) per quad of a grid of
+// (nb_slice_w + 1) x (nb_slice_h + 1) vertices stored row by row.
+fn grid_triangles(
+    nb_slice_w: usize,
+    nb_slice_h: usize,
+    vertex: &[Vector3],
+    flip_diagonals: bool,
+) -> Vec<(usize, usize, usize)> {
+    let row = nb_slice_w + 1;
+    let mut indices = Vec::with_capacity(nb_slice_w * nb_slice_h * 2);
+
+    for i in 0..nb_slice_h {
+        for j in 0..nb_slice_w {
+            let a = i * row + j;
+            let b = a + 1;
+            let c = a + row;
+            let d = c + 1;
+
+            let ad = (vertex[a].y - vertex[d].y).abs();
+            let bc = (vertex[b].y - vertex[c].y).abs();
+
+            if flip_diagonals && ad < bc {
+                indices.push((a, c, d));
+                indices.push((a, d, b));
+            } else {
+                indices.push((a, c, b));
+                indices.push((b, c, d));
             }
-        },
-        None => None,
+        }
+    }
+
+    indices
+}
+
+pub(crate) fn face_normal(a: &Vector3, b: &Vector3, c: &Vector3) -> Vector3 {
+    unit_or_up((b - a).vec_product(&(c - a)))
+}
+
+// degenerate faces and isolated vertices point up
+fn unit_or_up(mut v: Vector3) -> Vector3 {
+    v.normalize();
+    if v.is_zero() {
+        Vector3::from(0.0, 1.0, 0.0, 0)
+    } else {
+        v
     }
 }
@@ -4,3 +4,8 @@ pub mod line;
 pub mod graph;
 pub mod vector3;
 pub mod delaunay;
+pub mod simplify;
+pub mod geojson;
+pub mod gltf;
+pub mod stl;
+pub mod writer;
@@ -0,0 +1,351 @@
+// Greedy insertion terrain simplification (Garland & Heckbert): start from
+// the two triangles covering the grid, then repeatedly insert the grid point
+// with the largest vertical error into the Delaunay triangulation until every
+// point is within `tolerance` of the surface. Points are grid coordinates so
+// the orientation and circumcircle tests are exact integer predicates.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Point {
+    // column
+    x: i64,
+    // row
+    z: i64,
+}
+
+#[derive(Clone, Debug)]
+struct Triangle {
+    // counter-clockwise in the (x, z) plane
+    v: [usize; 3],
+    // n[k] is the neighbour across the edge opposite to v[k]
+    n: [Option<usize>; 3],
+    // bumped on every change so that stale heap entries can be skipped
+    version: usize,
+}
+
+struct Candidate {
+    error: f64,
+    triangle: usize,
+    version: usize,
+    point: Point,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.error.total_cmp(&other.error)
+    }
+}
+
+struct Triangulation<'a> {
+    heightmap: &'a Vec<Vec<f64>>,
+    points: Vec<Point>,
+    triangles: Vec<Triangle>,
+    heap: BinaryHeap<Candidate>,
+    // triangles changed by the current insertion
+    modified: Vec<usize>,
+}
+
+fn orient(a: Point, b: Point, c: Point) -> i64 {
+    (b.x - a.x) * (c.z - a.z) - (b.z - a.z) * (c.x - a.x)
+}
+
+// > 0 when d is strictly inside the circumcircle of the ccw triangle abc
+fn in_circle(a: Point, b: Point, c: Point, d: Point) -> bool {
+    let (adx, adz) = ((a.x - d.x) as i128, (a.z - d.z) as i128);
+    let (bdx, bdz) = ((b.x - d.x) as i128, (b.z - d.z) as i128);
+    let (cdx, cdz) = ((c.x - d.x) as i128, (c.z - d.z) as i128);
+
+    let ad = adx * adx + adz * adz;
+    let bd = bdx * bdx + bdz * bdz;
+    let cd = cdx * cdx + cdz * cdz;
+
+    let det = adx * (bdz * cd - bd * cdz) - adz * (bdx * cd - bd * cdx)
+        + ad * (bdx * cdz - bdz * cdx);
+
+    det > 0
+}
+
+impl<'a> Triangulation<'a> {
+    fn new(heightmap: &'a Vec<Vec<f64>>, nb_x: usize, nb_z: usize) -> Self {
+        let (w, h) = (nb_x as i64 - 1, nb_z as i64 - 1);
+        let points = vec![
+            Point { x: 0, z: 0 },
+            Point { x: w, z: 0 },
+            Point { x: w, z: h },
+            Point { x: 0, z: h },
+        ];
+
+        let triangles = vec![
+            Triangle {
+                v: [0, 1, 2],
+                n: [None, Some(1), None],
+                version: 0,
+            },
+            Triangle {
+                v: [0, 2, 3],
+                n: [None, None, Some(0)],
+                version: 0,
+            },
+        ];
+
+        let mut res = Self {
+            heightmap,
+            points,
+            triangles,
+            heap: BinaryHeap::new(),
+            modified: Vec::new(),
+        };
+
+        res.update_candidate(0);
+        res.update_candidate(1);
+        res.modified.clear();
+        res
+    }
+
+    fn height(&self, p: Point) -> f64 {
+        self.heightmap[p.z as usize][p.x as usize]
+    }
+
+    fn replace_neighbour(&mut self, t: Option<usize>, old: usize, new: usize) {
+        if let Some(t) = t {
+            for k in 0..3 {
+                if self.triangles[t].n[k] == Some(old) {
+                    self.triangles[t].n[k] = Some(new);
+                }
+            }
+        }
+    }
+
+    // rotates the triangle so that v[k] becomes v[0]
+    fn rotate(&mut self, t: usize, k: usize) {
+        let tri = &mut self.triangles[t];
+        tri.v.rotate_left(k);
+        tri.n.rotate_left(k);
+    }
+
+    fn push(&mut self, v: [usize; 3], n: [Option<usize>; 3]) -> usize {
+        self.triangles.push(Triangle { v, n, version: 0 });
+        self.modified.push(self.triangles.len() - 1);
+        self.triangles.len() - 1
+    }
+
+    fn set(&mut self, t: usize, v: [usize; 3], n: [Option<usize>; 3]) {
+        let tri = &mut self.triangles[t];
+        tri.v = v;
+        tri.n = n;
+        tri.version += 1;
+        self.modified.push(t);
+    }
+
+    // grid point of the triangle furthest from its plane
+    fn update_candidate(&mut self, t: usize) {
+        let [a, b, c] = self.triangles[t].v.map(|i| self.points[i]);
+        let (ha, hb, hc) = (self.height(a), self.height(b), self.height(c));
+        let area = orient(a, b, c) as f64;
+
+        let min_x = a.x.min(b.x).min(c.x);
+        let max_x = a.x.max(b.x).max(c.x);
+        let min_z = a.z.min(b.z).min(c.z);
+        let max_z = a.z.max(b.z).max(c.z);
+
+        let mut best: Option<(f64, Point)> = None;
+
+        for z in min_z..=max_z {
+            for x in min_x..=max_x {
+                let p = Point { x, z };
+                let wa = orient(b, c, p);
+                let wb = orient(c, a, p);
+                let wc = orient(a, b, p);
+
+                if wa < 0 || wb < 0 || wc < 0 || p == a || p == b || p == c {
+                    continue;
+                }
+
+                let interpolated =
+                    (wa as f64 * ha + wb as f64 * hb + wc as f64 * hc) / area;
+                let error = (self.height(p) - interpolated).abs();
+
+                if best.map_or(true, |(e, _)| error > e) {
+                    best = Some((error, p));
+                }
+            }
+        }
+
+        if let Some((error, point)) = best {
+            self.heap.push(Candidate {
+                error,
+                triangle: t,
+                version: self.triangles[t].version,
+                point,
+            });
+        }
+    }
+
+    // checks the edge of t opposite to the vertex p
+    fn legalize(&mut self, t: usize, p: usize) {
+        let k = self.triangles[t].v.iter().position(|&v| v == p).unwrap();
+        self.rotate(t, k);
+        let Some(n) = self.triangles[t].n[0] else {
+            return;
+        };
+
+        let m = (0..3).find(|&m| self.triangles[n].n[m] == Some(t)).unwrap();
+        self.rotate(n, m);
+
+        // t = [p, b, c], n = [q, c, b]
+        let [p, b, c] = self.triangles[t].v;
+        let q = self.triangles[n].v[0];
+        let [_, t_b, t_c] = self.triangles[t].n;
+        let [_, n_c, n_b] = self.triangles[n].n;
+
+        let [pp, pb, pc, pq] = [p, b, c, q].map(|i| self.points[i]);
+        if !in_circle(pp, pb, pc, pq) {
+            return;
+        }
+
+        // flip the edge bc into pq: t = [p, b, q], n = [p, q, c]
+        self.set(t, [p, b, q], [n_c, Some(n), t_c]);
+        self.set(n, [p, q, c], [n_b, t_b, Some(t)]);
+        self.replace_neighbour(n_c, n, t);
+        self.replace_neighbour(t_b, t, n);
+
+        self.legalize(t, p);
+        self.legalize(n, p);
+    }
+
+    fn insert(&mut self, t: usize, p: Point) {
+        let [a, b, c] = self.triangles[t].v.map(|i| self.points[i]);
+        let on_edge = [orient(b, c, p), orient(c, a, p), orient(a, b, p)]
+            .iter()
+            .position(|&w| w == 0);
+
+        self.points.push(p);
+        let pi = self.points.len() - 1;
+
+        let created = match on_edge {
+            None => self.split_triangle(t, pi),
+            Some(k) => self.split_edge(t, k, pi),
+        };
+
+        for t in created {
+            self.legalize(t, pi);
+        }
+
+        let mut modified = std::mem::take(&mut self.modified);
+        modified.sort_unstable();
+        modified.dedup();
+
+        for t in modified {
+            self.update_candidate(t);
+        }
+    }
+
+    // p strictly inside t = [a, b, c]
+    fn split_triangle(&mut self, t: usize, p: usize) -> Vec<usize> {
+        let [a, b, c] = self.triangles[t].v;
+        let [na, nb, nc] = self.triangles[t].n;
+
+        let t2 = self.push([p, c, a], [nb, None, None]);
+        let t3 = self.push([p, a, b], [nc, None, None]);
+
+        self.set(t, [p, b, c], [na, Some(t2), Some(t3)]);
+        self.triangles[t2].n = [nb, Some(t3), Some(t)];
+        self.triangles[t3].n = [nc, Some(t), Some(t2)];
+
+        self.replace_neighbour(nb, t, t2);
+        self.replace_neighbour(nc, t, t3);
+
+        vec![t, t2, t3]
+    }
+
+    // p on the edge of t opposite to v[k], the neighbour is split as well
+    fn split_edge(&mut self, t: usize, k: usize, p: usize) -> Vec<usize> {
+        self.rotate(t, k);
+        let [a, b, c] = self.triangles[t].v;
+        let [n, t_b, t_c] = self.triangles[t].n;
+
+        // t = [a, b, p], t2 = [a, p, c]
+        let t2 = self.push([a, p, c], [None, t_b, Some(t)]);
+        self.set(t, [a, b, p], [None, Some(t2), t_c]);
+        self.replace_neighbour(t_b, t, t2);
+
+        let Some(n) = n else {
+            return vec![t, t2];
+        };
+
+        let m = (0..3).find(|&m| self.triangles[n].n[m] == Some(t)).unwrap();
+        self.rotate(n, m);
+
+        // n = [d, c, b] becomes n = [d, c, p], n2 = [d, p, b]
+        let d = self.triangles[n].v[0];
+        let [_, n_c, n_b] = self.triangles[n].n;
+
+        let n2 = self.push([d, p, b], [Some(t), n_c, Some(n)]);
+        self.set(n, [d, c, p], [Some(t2), Some(n2), n_b]);
+        self.replace_neighbour(n_c, n, n2);
+
+        self.triangles[t].n[0] = Some(n2);
+        self.triangles[t2].n[0] = Some(n);
+
+        vec![t, t2, n, n2]
+    }
+
+    fn run(&mut self, tolerance: f64, max_points: usize) {
+        while let Some(candidate) = self.heap.pop() {
+            if candidate.error <= tolerance || self.points.len() >= max_points {
+                break;
+            }
+
+            if candidate.version != self.triangles[candidate.triangle].version {
+                continue;
+            }
+
+            self.insert(candidate.triangle, candidate.point);
+        }
+    }
+}
+
+// Simplified triangulation of a heightmap indexed as `heightmap[z][x]`.
+// Returns the kept grid points as (row, column) and triangles indexing them,
+// counter-clockwise when seen from above (+y).
+pub fn simplify_heightmap(
+    heightmap: &Vec<Vec<f64>>,
+    nb_x: usize,
+    nb_z: usize,
+    tolerance: f64,
+    max_points: usize,
+) -> (Vec<(usize, usize)>, Vec<(usize, usize, usize)>) {
+    let mut triangulation = Triangulation::new(heightmap, nb_x, nb_z);
+    triangulation.run(tolerance, max_points);
+
+    let points = triangulation
+        .points
+        .iter()
+        .map(|p| (p.z as usize, p.x as usize))
+        .collect();
+
+    // ccw in (x, z) is clockwise seen from +y
+    let triangles = triangulation
+        .triangles
+        .iter()
+        .map(|t| (t.v[0], t.v[2], t.v[1]))
+        .collect();
+
+    (points, triangles)
+}
@@ -0,0 +1,89 @@
+use crate::obj_export::mesh::mesh::{face_normal, Mesh};
+use crate::obj_export::vector3::vector3::Vector3;
+use crate::obj_export::writer::writer::{create_file, ExportError};
+
+use std::collections::BTreeSet;
+use std::io::prelude::*;
+
+// the mesh is y up, STL files are read as z up
+fn z_up(v: &Vector3) -> [f32; 3] {
+    [v.x as f32, -v.z as f32, v.y as f32]
+}
+
+impl Mesh {
+    // Closes the surface into a watertight solid: every boundary edge gets a
+    // vertical skirt down to a flat base `base_thickness` under the lowest
+    // point. The base is a fan around the center of the outline, which is
+    // fine for the rectangle of a terrain.
+    pub fn solid(&self, base_thickness: f64) -> Mesh {
+        let mut res = self.welded();
+
+        let floor = res.vertex.iter().map(|v| v.y).fold(f64::MAX, f64::min)
+            - base_thickness;
+        let base = res.add_skirts(|_| floor);
+
+        if !base.is_empty() {
+            let outline: BTreeSet<usize> = base.iter().map(|x| x.0).collect();
+            let (x, z) = outline.iter().fold((0.0, 0.0), |(x, z), &i| {
+                (x + res.vertex[i].x, z + res.vertex[i].z)
+            });
+            let count = outline.len() as f64;
+            let center = res.vertex.len();
+            res.vertex
+                .push(Vector3::from(x / count, floor, z / count, center));
+
+            for (a, b) in base {
+                res.indices.push((center, b, a));
+            }
+        }
+
+        res.compute_normals(None);
+        res
+    }
+
+    pub fn write_stl<W: Write>(
+        &self,
+        mut out: W,
+        binary: bool,
+    ) -> Result<(), ExportError> {
+        let facets = self.indices.iter().map(|&(a, b, c)| {
+            let (a, b, c) = (&self.vertex[a], &self.vertex[b], &self.vertex[c]);
+            (z_up(&face_normal(a, b, c)), [z_up(a), z_up(b), z_up(c)])
+        });
+
+        if binary {
+            let mut header = [0u8; 80];
+            let name = b"pimp_my_map terrain";
+            header[..name.len()].copy_from_slice(name);
+            out.write_all(&header)?;
+            out.write_all(&(self.indices.len() as u32).to_le_bytes())?;
+
+            for (normal, points) in facets {
+                for x in normal.iter().chain(points.iter().flatten()) {
+                    out.write_all(&x.to_le_bytes())?;
+                }
+                // attribute byte count
+                out.write_all(&0u16.to_le_bytes())?;
+            }
+        } else {
+            writeln!(out, "solid terrain")?;
+            for (n, points) in facets {
+                writeln!(out, "facet normal {} {} {}", n[0], n[1], n[2])?;
+                writeln!(out, "  outer loop")?;
+                for p in points {
+                    writeln!(out, "    vertex {} {} {}", p[0], p[1], p[2])?;
+                }
+                writeln!(out, "  endloop")?;
+                writeln!(out, "endfacet")?;
+            }
+            writeln!(out, "endsolid terrain")?;
+        }
+
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn to_stl(&self, path: &str, binary: bool) -> Result<(), ExportError> {
+        self.write_stl(create_file(path)?, binary)
+    }
+}
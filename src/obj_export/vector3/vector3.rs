@@ -37,18 +37,50 @@ impl Vector3 {
             0)
     }
 
+    pub fn dot(&self, other: &Self) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
     pub fn normalize(&mut self){
-        if self.x != 0.0 {
-            self.x = self.x / self.x;
+        let length = self.length();
+        if length != 0.0 {
+            self.x /= length;
+            self.y /= length;
+            self.z /= length;
         }
+    }
+}
 
-        if self.y != 0.0 {
-            self.y = self.y / self.y;
-        }
+impl std::ops::Add for Vector3 {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        Vector3::from(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z, 0)
+    }
+}
 
-        if self.z != 0.0 {
-            self.z = self.z / self.z;
-        }
+impl std::ops::AddAssign<&Vector3> for Vector3 {
+    fn add_assign(&mut self, rhs: &Self) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+        self.z += rhs.z;
+    }
+}
+
+impl std::ops::Sub for &Vector3 {
+    type Output = Vector3;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vector3::from(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z, 0)
+    }
+}
+
+impl std::ops::Mul<f64> for Vector3 {
+    type Output = Self;
+    fn mul(self, rhs: f64) -> Self::Output {
+        Vector3::from(self.x * rhs, self.y * rhs, self.z * rhs, 0)
     }
 }
 
@@ -0,0 +1,104 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+// kind of surface a mesh stands for, it gives its name and its material
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Surface {
+    Terrain,
+    Water,
+}
+
+impl Surface {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Surface::Terrain => "terrain",
+            Surface::Water => "water",
+        }
+    }
+
+    // rgba, multiplied by the texture on textured surfaces
+    pub fn color(&self) -> [f64; 4] {
+        match self {
+            Surface::Terrain => [1.0, 1.0, 1.0, 1.0],
+            Surface::Water => [0.15, 0.35, 0.6, 0.7],
+        }
+    }
+
+    pub fn textured(&self) -> bool {
+        *self == Surface::Terrain
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    InvalidPath(String),
+    // image to texture the export with, where it was looked for
+    MissingTexture(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "Error while writing: {e}"),
+            ExportError::Json(e) => write!(f, "Error while serializing: {e}"),
+            ExportError::InvalidPath(path) => write!(f, "Invalid path: {path}"),
+            ExportError::MissingTexture(path) => {
+                write!(f, "Texture not found: {path}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExportError::Io(e) => Some(e),
+            ExportError::Json(e) => Some(e),
+            ExportError::InvalidPath(_) | ExportError::MissingTexture(_) => {
+                None
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ExportError {
+    fn from(e: serde_json::Error) -> Self {
+        ExportError::Json(e)
+    }
+}
+
+// buffered file every `to_*` method writes through
+pub fn create_file<P: AsRef<Path>>(
+    path: P,
+) -> Result<BufWriter<File>, ExportError> {
+    Ok(BufWriter::new(File::create(path)?))
+}
+
+// `path` with another extension, and the file name to reference it with
+pub fn sibling(
+    path: &str,
+    extension: &str,
+) -> Result<(String, String), ExportError> {
+    let sibling = Path::new(path).with_extension(extension);
+    let name = sibling
+        .file_name()
+        .and_then(|x| x.to_str())
+        .ok_or(ExportError::InvalidPath(path.to_string()))?
+        .to_string();
+    let sibling = sibling
+        .to_str()
+        .ok_or(ExportError::InvalidPath(path.to_string()))?
+        .to_string();
+
+    Ok((sibling, name))
+}
@@ -11,8 +11,8 @@ const WINDOW_WIDTH: usize = 480;
 // const CHUNK_COUNT: usize = 8;
 
 const OCTAVES: usize = 8;
-const FALLOUT: f32 = 0.5;
-const LACUNARITY: f32 = 2.0;
+const FALLOUT: f64 = 0.5;
+const LACUNARITY: f64 = 2.0;
 const FREQ: f32 = 0.005;
 
 #[derive(Clone)]
@@ -43,18 +43,15 @@ fn build_chunks(dims: (usize, usize), chunk_size: usize) -> Vec<Chunk> {
     return chunks;
 }
 
-fn color_chunks(chunks: &mut Vec<Chunk>, permuts: &[usize; 512]) {
+fn color_chunks(chunks: &mut Vec<Chunk>, opts: &PerlinOpts) {
     for chunk in chunks.iter_mut() {
         chunk.color = {
             let gray: u8 = (perlin3d(&PerlinOpts {
-                x: chunk.coords.x * chunk.rect.width * FREQ,
-                y: chunk.coords.y * chunk.rect.width * FREQ,
-                z: 1.0,
-                base: 0.0,
-                permutations: *permuts,
-                octaves: OCTAVES,
-                fallout: FALLOUT,
-                lacunarity: LACUNARITY,
+                x: chunk.coords.x * chunk.rect.width * opts.frequency
+                    + opts.offset_x,
+                y: chunk.coords.y * chunk.rect.width * opts.frequency
+                    + opts.offset_y,
+                ..opts.clone()
             }) * 255.0) as u8;
 
             rcolor(gray, gray, gray, 255)
@@ -85,15 +82,22 @@ pub fn main() {
     let dims: (usize, usize) = (WINDOW_HEIGHT as usize, WINDOW_WIDTH as usize);
     let mut chunks = build_chunks(dims, chunk_size);
 
-    let mut permuts: [usize; 512] = random_permutations();
+    let mut opts = PerlinOpts {
+        permutations: random_permutations(),
+        octaves: OCTAVES,
+        fallout: FALLOUT,
+        lacunarity: LACUNARITY,
+        frequency: FREQ,
+        ..PerlinOpts::default()
+    };
     rl.set_target_fps(60);
 
     while !rl.window_should_close() {
         let mut d = rl.begin_drawing(&thread);
 
         if d.is_key_pressed(KeyboardKey::KEY_SPACE) {
-            color_chunks(&mut chunks, &permuts);
-            permuts = random_permutations();
+            color_chunks(&mut chunks, &opts);
+            opts.permutations = random_permutations();
         }
 
         if d.is_window_resized() {}
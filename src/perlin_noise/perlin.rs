@@ -1,15 +1,26 @@
 use rand::distributions::{Distribution, Uniform};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PerlinOpts {
     pub x: f32,
     pub y: f32,
     pub z: f32,
     pub base: f64,
+    // regenerated before every generation
+    #[serde(skip, default = "no_permutations")]
     pub permutations: [usize; 512],
     pub octaves: usize,
     pub fallout: f64,
     pub lacunarity: f64,
+    // mapping of the voxel coordinates, in map units, to the noise space
+    pub frequency: f32,
+    pub offset_x: f32,
+    pub offset_y: f32,
+    // the permutations are drawn from it
+    pub seed: u64,
 }
 
 impl Default for PerlinOpts {
@@ -23,10 +34,18 @@ impl Default for PerlinOpts {
             octaves: 8,
             fallout: 0.5,
             lacunarity: 2.0,
+            frequency: 1.0,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            seed: 0,
         }
     }
 }
 
+fn no_permutations() -> [usize; 512] {
+    [0; 512]
+}
+
 fn lerp(a: f64, b: f64, w: f64) -> f64 {
     (1.0 - w) * a + b * w
 }
@@ -37,15 +56,18 @@ fn fade(t: f64) -> f64 {
 }
 
 pub fn random_permutations() -> [usize; 512] {
+    seeded_permutations(rand::random())
+}
+
+pub fn seeded_permutations(seed: u64) -> [usize; 512] {
     let mut perm = [0; 512];
-    let mut rng = rand::thread_rng();
+    let mut rng = StdRng::seed_from_u64(seed);
 
     for i in 0..256 {
         perm[i] = i;
     }
 
     for i in 0..256 {
-        // let j = rng.gen_range(0..256) & 0xFF;
         let j = Uniform::from(0..256).sample(&mut rng) & 0xFF;
         perm.swap(j, i);
     }
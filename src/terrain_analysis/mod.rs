@@ -0,0 +1 @@
+pub mod terrain_analysis;
@@ -0,0 +1,182 @@
+// Every grid here has the shape of the heightmap it was computed from:
+// `heightmap[i][j]` with altitudes in [0, 1]. `z_scale` converts an altitude
+// into cells, so that a slope of 1 means one cell up for one cell across.
+
+use serde::{Deserialize, Serialize};
+
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Layer {
+    Slope,
+    Aspect,
+    Curvature,
+    FlowAccumulation,
+}
+
+// height at (i, j), borders are extended
+fn at(heightmap: &Vec<Vec<f64>>, i: isize, j: isize) -> f64 {
+    let rows = heightmap.len() as isize;
+    let cols = heightmap[0].len() as isize;
+    heightmap[i.clamp(0, rows - 1) as usize][j.clamp(0, cols - 1) as usize]
+}
+
+// Horn's 3x3 gradient, (d/di, d/dj)
+fn gradient(heightmap: &Vec<Vec<f64>>, i: usize, j: usize) -> (f64, f64) {
+    let (i, j) = (i as isize, j as isize);
+    let h = |di: isize, dj: isize| at(heightmap, i + di, j + dj);
+
+    let di = ((h(1, -1) + 2.0 * h(1, 0) + h(1, 1))
+        - (h(-1, -1) + 2.0 * h(-1, 0) + h(-1, 1)))
+        / 8.0;
+    let dj = ((h(-1, 1) + 2.0 * h(0, 1) + h(1, 1))
+        - (h(-1, -1) + 2.0 * h(0, -1) + h(1, -1)))
+        / 8.0;
+
+    (di, dj)
+}
+
+fn map_cells(
+    heightmap: &Vec<Vec<f64>>,
+    f: impl Fn(usize, usize) -> f64,
+) -> Vec<Vec<f64>> {
+    (0..heightmap.len())
+        .map(|i| (0..heightmap[i].len()).map(|j| f(i, j)).collect())
+        .collect()
+}
+
+// steepness in degrees, 0 is flat and 90 a vertical cliff
+pub fn slope(heightmap: &Vec<Vec<f64>>, z_scale: f64) -> Vec<Vec<f64>> {
+    map_cells(heightmap, |i, j| {
+        let (di, dj) = gradient(heightmap, i, j);
+        ((di * di + dj * dj).sqrt() * z_scale).atan().to_degrees()
+    })
+}
+
+// downhill direction in degrees clockwise from north (row 0 side),
+// -1 on flat cells
+pub fn aspect(heightmap: &Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    map_cells(heightmap, |i, j| {
+        let (di, dj) = gradient(heightmap, i, j);
+        if di == 0.0 && dj == 0.0 {
+            return -1.0;
+        }
+
+        // downhill is -gradient, north is -i and east is +j
+        let res = (-dj).atan2(di).to_degrees();
+        if res < 0.0 {
+            res + 360.0
+        } else {
+            res
+        }
+    })
+}
+
+// negative laplacian, > 0 on ridges and peaks, < 0 in valleys and pits
+pub fn curvature(heightmap: &Vec<Vec<f64>>, z_scale: f64) -> Vec<Vec<f64>> {
+    map_cells(heightmap, |i, j| {
+        let (i, j) = (i as isize, j as isize);
+        let h = |di: isize, dj: isize| at(heightmap, i + di, j + dj);
+
+        -(h(-1, 0) + h(1, 0) + h(0, -1) + h(0, 1) - 4.0 * h(0, 0)) * z_scale
+    })
+}
+
+// D8 flow accumulation: every cell drains into its steepest lower
+// neighbour, the result is the number of cells draining through each cell
+// (itself included). Pits keep their water.
+pub fn flow_accumulation(heightmap: &Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    let rows = heightmap.len();
+    let cols = if rows > 0 { heightmap[0].len() } else { 0 };
+    let mut res = vec![vec![1.0; cols]; rows];
+
+    let mut order: Vec<(usize, usize)> = (0..rows)
+        .flat_map(|i| (0..cols).map(move |j| (i, j)))
+        .collect();
+    order.sort_by(|a, b| heightmap[b.0][b.1].total_cmp(&heightmap[a.0][a.1]));
+
+    for (i, j) in order {
+        let mut target = None;
+        let mut steepest = 0.0;
+
+        for (di, dj) in NEIGHBOURS {
+            let (ni, nj) = (i as isize + di, j as isize + dj);
+            if ni < 0 || nj < 0 || ni >= rows as isize || nj >= cols as isize {
+                continue;
+            }
+
+            let (ni, nj) = (ni as usize, nj as usize);
+            let distance = if di != 0 && dj != 0 {
+                std::f64::consts::SQRT_2
+            } else {
+                1.0
+            };
+            let drop = (heightmap[i][j] - heightmap[ni][nj]) / distance;
+
+            if drop > steepest {
+                steepest = drop;
+                target = Some((ni, nj));
+            }
+        }
+
+        if let Some((ni, nj)) = target {
+            res[ni][nj] += res[i][j];
+        }
+    }
+
+    res
+}
+
+pub fn compute_layer(
+    heightmap: &Vec<Vec<f64>>,
+    layer: Layer,
+    z_scale: f64,
+) -> Vec<Vec<f64>> {
+    match layer {
+        Layer::Slope => slope(heightmap, z_scale),
+        Layer::Aspect => aspect(heightmap),
+        Layer::Curvature => curvature(heightmap, z_scale),
+        Layer::FlowAccumulation => flow_accumulation(heightmap),
+    }
+}
+
+// rescales a layer into [0, 1] for display, flow accumulation spans several
+// orders of magnitude and is shown on a log scale
+pub fn layer_to_unit(grid: &Vec<Vec<f64>>, layer: Layer) -> Vec<Vec<f64>> {
+    if grid.is_empty() {
+        return Vec::new();
+    }
+
+    let values: Vec<Vec<f64>> = match layer {
+        Layer::FlowAccumulation => grid
+            .iter()
+            .map(|row| row.iter().map(|x| x.ln()).collect())
+            .collect(),
+        _ => grid.clone(),
+    };
+
+    let (min, max) = values
+        .iter()
+        .flatten()
+        .fold((f64::MAX, f64::MIN), |(min, max), &x| {
+            (min.min(x), max.max(x))
+        });
+
+    if max <= min {
+        return vec![vec![0.0; grid[0].len()]; grid.len()];
+    }
+
+    values
+        .iter()
+        .map(|row| row.iter().map(|x| (x - min) / (max - min)).collect())
+        .collect()
+}
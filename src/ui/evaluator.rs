@@ -0,0 +1,527 @@
+// Incremental evaluation of the graph. The output of every node is cached
+// along with the options and the inputs it was computed from, a node is only
+// computed again when one of them changed, which in turn changes the input of
+// the nodes downstream. The computations run in a background thread on
+// copies of the widgets, and are dropped as soon as the graph changes under
+// them.
+//
+// The first input plug of a node is written to the voxel map given to
+// `apply`, the values of the other ones go to `Globals::inputs`.
+
+use crate::display::build_2D::{biome_color, BiomeOpts};
+use crate::display::settlements::{Road, Settlement};
+use crate::history::*;
+use crate::multimap::Index;
+use crate::ui::ui::Globals;
+use crate::voxels::VoxelMap;
+use crate::widgets::map::*;
+use crate::widgets::*;
+
+use raylib::prelude::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+// side of the thumbnails shown on the blocks, in pixels
+const THUMBNAIL_SIZE: usize = 48;
+
+// what `apply` leaves in the voxel map and in the globals
+#[derive(Clone)]
+pub struct NodeOutput {
+    // one value per voxel, in the order of the voxel map
+    pub values: Vec<f64>,
+    pub biomes: bool,
+    pub biome_opts: BiomeOpts,
+    pub altmap: Vec<Vec<f64>>,
+    pub settlements: Vec<Settlement>,
+    pub roads: Vec<Road>,
+}
+
+// output of a node and the grid the widget kept, see `take_grid`
+type Message = (Index, Arc<NodeOutput>, Vec<Vec<f64>>);
+
+impl NodeOutput {
+    pub fn read(voxmap: &VoxelMap, globals: &Globals) -> Self {
+        Self {
+            values: voxmap.voxels.iter().map(|x| x.value).collect(),
+            biomes: voxmap.biomes,
+            biome_opts: globals.biome_opts.clone(),
+            altmap: globals.altmap.clone(),
+            settlements: globals.settlements.clone(),
+            roads: globals.roads.clone(),
+        }
+    }
+
+    pub fn write(&self, voxmap: &mut VoxelMap, globals: &mut Globals) {
+        voxmap
+            .voxels
+            .iter_mut()
+            .zip(self.values.iter())
+            .for_each(|(x, y)| x.value = *y);
+        voxmap.biomes = self.biomes;
+        globals.biome_opts = self.biome_opts.clone();
+        globals.altmap = self.altmap.clone();
+        globals.settlements = self.settlements.clone();
+        globals.roads = self.roads.clone();
+    }
+
+    // downsampled image of the values, `voxmap` gives their layout
+    pub fn thumbnail(&self, voxmap: &VoxelMap) -> Image {
+        let elx = (voxmap.dims.x / voxmap.res.x) as usize;
+        let ely = (voxmap.dims.y / voxmap.res.y) as usize;
+        let mut img = Image::gen_image_color(
+            THUMBNAIL_SIZE as i32,
+            THUMBNAIL_SIZE as i32,
+            Color::BLACK,
+        );
+
+        for j in 0..THUMBNAIL_SIZE {
+            for i in 0..THUMBNAIL_SIZE {
+                let value = match self.values.get(
+                    j * ely / THUMBNAIL_SIZE * elx + i * elx / THUMBNAIL_SIZE,
+                ) {
+                    Some(value) => *value,
+                    None => continue,
+                };
+                let color = if self.biomes {
+                    biome_color(value)
+                } else {
+                    let col = (value.clamp(0.0, 1.0) * 255.0) as u8;
+                    Color::new(col, col, col, 255)
+                };
+
+                img.draw_pixel(i as i32, j as i32, color);
+            }
+        }
+
+        img
+    }
+}
+
+struct CacheEntry {
+    // stamps of the outputs it was computed from, one per input plug, 0 for
+    // the unplugged ones
+    inputs: Vec<u64>,
+    opts: WidgetOpts,
+    stamp: u64,
+    output: Arc<NodeOutput>,
+}
+
+enum Input {
+    Unplugged,
+    Cached(Arc<NodeOutput>),
+    // output of an earlier job
+    Job(Index),
+}
+
+struct Job {
+    index: Index,
+    snapshot: WidgetSnapshot,
+    inputs: Vec<Input>,
+}
+
+pub struct Evaluator {
+    cache: HashMap<Index, CacheEntry>,
+    next_stamp: u64,
+    // entries of the jobs sent to the worker, waiting for their output
+    pending: HashMap<Index, (Vec<u64>, WidgetOpts, u64)>,
+    worker: Option<Receiver<Message>>,
+    // set to stop the worker before its next job
+    cancelled: Arc<AtomicBool>,
+    // stamp of the output shown in the previewer
+    shown: u64,
+    // evaluate on every change, otherwise only when requested
+    pub live: bool,
+    requested: bool,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self {
+            cache: HashMap::new(),
+            next_stamp: 1,
+            pending: HashMap::new(),
+            worker: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            shown: 0,
+            live: true,
+            requested: false,
+        }
+    }
+}
+
+impl Evaluator {
+    // everything is computed again on the next evaluation
+    pub fn invalidate(&mut self) {
+        self.cache.clear();
+    }
+
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    // last output of every node along with its stamp
+    pub fn outputs(&self) -> impl Iterator<Item = (Index, u64, &NodeOutput)> {
+        self.cache
+            .iter()
+            .map(|(index, entry)| (*index, entry.stamp, entry.output.as_ref()))
+    }
+
+    // Collects the outputs of the worker and starts the next evaluation when
+    // the graph changed. Returns true when the output of the graph was
+    // written to `voxmap` and `globals`.
+    pub fn update(
+        &mut self,
+        objmap: &mut ObjMap,
+        voxmap: &mut VoxelMap,
+        globals: &mut Globals,
+    ) -> bool {
+        let mut finished = false;
+
+        while let Some(worker) = &self.worker {
+            match worker.try_recv() {
+                Ok((index, output, grid)) => {
+                    self.store(objmap, index, output, grid)
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.worker = None;
+                    self.pending.clear();
+                    finished = true;
+                }
+            }
+        }
+
+        if !(self.live || self.requested) {
+            return false;
+        }
+
+        let order = match evaluation_order(objmap) {
+            Ok(order) => order,
+            Err(_) => {
+                self.requested = false;
+                return false;
+            }
+        };
+
+        // the jobs left are computed again from the new graph
+        if self.worker.is_some() {
+            if !self.outdated(objmap, &order) {
+                return false;
+            }
+            self.cancel();
+        }
+
+        self.cache.retain(|x, _| order.contains(x));
+        let jobs = self.plan(objmap, &order);
+
+        if !jobs.is_empty() {
+            self.spawn(jobs, voxmap);
+            return false;
+        }
+
+        if finished && self.requested {
+            eprintln!("Generating... DONE");
+        }
+        self.requested = false;
+
+        match order.last().and_then(|x| self.cache.get(x)) {
+            Some(entry) if entry.stamp != self.shown => {
+                entry.output.write(voxmap, globals);
+                self.shown = entry.stamp;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn store(
+        &mut self,
+        objmap: &mut ObjMap,
+        index: Index,
+        output: Arc<NodeOutput>,
+        grid: Vec<Vec<f64>>,
+    ) {
+        let (inputs, opts, stamp) = match self.pending.remove(&index) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        if let Some(widget) = objmap
+            .objs
+            .data_get_mut(&index)
+            .and_then(|x| x.as_widget_plugable_mut())
+        {
+            widget.set_grid(grid);
+        }
+
+        self.cache.insert(
+            index,
+            CacheEntry {
+                inputs,
+                opts,
+                stamp,
+                output,
+            },
+        );
+    }
+
+    fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.worker = None;
+        self.pending.clear();
+    }
+
+    // The options or the inputs of a node differ from the ones of its cached
+    // output and of the job computing it.
+    fn outdated(&self, objmap: &ObjMap, order: &[Index]) -> bool {
+        let mut stamps = HashMap::<Index, u64>::new();
+
+        for index in order.iter() {
+            let widget = match objmap.objs.data_get(index) {
+                Some(widget) => widget,
+                None => continue,
+            };
+
+            let (_, inputs, opts) = node_key(objmap, *index, &stamps);
+            let stamp = match (self.pending.get(index), self.cache.get(index)) {
+                (Some((x, y, stamp)), _) if *x == inputs && *y == opts => stamp,
+                (_, Some(x)) if x.inputs == inputs && x.opts == opts => {
+                    &x.stamp
+                }
+                // never computed, see `plan`
+                _ if WidgetSnapshot::take(widget.as_ref()).is_none() => {
+                    continue
+                }
+                _ => return true,
+            };
+
+            stamps.insert(*index, *stamp);
+        }
+
+        false
+    }
+
+    // jobs of the nodes whose options or inputs changed
+    fn plan(&mut self, objmap: &ObjMap, order: &[Index]) -> Vec<Job> {
+        let mut stamps = HashMap::<Index, u64>::new();
+        let mut jobs = Vec::new();
+
+        for index in order.iter() {
+            let widget = match objmap.objs.data_get(index) {
+                Some(widget) => widget,
+                None => continue,
+            };
+
+            let (sources, inputs, opts) = node_key(objmap, *index, &stamps);
+
+            if let Some(entry) = self.cache.get(index) {
+                if entry.inputs == inputs && entry.opts == opts {
+                    stamps.insert(*index, entry.stamp);
+                    continue;
+                }
+            }
+
+            let snapshot = match WidgetSnapshot::take(widget.as_ref()) {
+                Some(snapshot) => snapshot,
+                None => continue,
+            };
+
+            let job_inputs = sources
+                .into_iter()
+                .map(|x| match x {
+                    None => Input::Unplugged,
+                    Some(x) if self.pending.contains_key(&x) => Input::Job(x),
+                    Some(x) => {
+                        self.cache.get(&x).map_or(Input::Unplugged, |x| {
+                            Input::Cached(x.output.clone())
+                        })
+                    }
+                })
+                .collect();
+
+            let stamp = self.next_stamp;
+            self.next_stamp += 1;
+            stamps.insert(*index, stamp);
+            self.pending.insert(*index, (inputs, opts, stamp));
+
+            jobs.push(Job {
+                index: *index,
+                snapshot,
+                inputs: job_inputs,
+            });
+        }
+
+        jobs
+    }
+
+    fn spawn(&mut self, jobs: Vec<Job>, voxmap: &VoxelMap) {
+        let (tx, rx) = mpsc::channel();
+        let dims = voxmap.dims;
+        let res = voxmap.res;
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        self.cancelled = cancelled.clone();
+        thread::spawn(move || run_jobs(jobs, dims, res, tx, cancelled));
+        self.worker = Some(rx);
+    }
+}
+
+// nodes linked to the input plugs of the node with a stamp, the stamps of
+// their outputs, 0 for the others, and the options of the node
+fn node_key(
+    objmap: &ObjMap,
+    index: Index,
+    stamps: &HashMap<Index, u64>,
+) -> (Vec<Option<Index>>, Vec<u64>, WidgetOpts) {
+    let sources: Vec<Option<Index>> = objmap
+        .get_input_sources(index)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|x| x.filter(|x| stamps.contains_key(x)))
+        .collect();
+    let inputs = sources
+        .iter()
+        .map(|x| x.map_or(0, |x| stamps[&x]))
+        .collect();
+    let opts = objmap
+        .objs
+        .data_get(&index)
+        .and_then(|x| x.as_widget_configurable())
+        .map_or(WidgetOpts::None, |x| x.get_opts());
+
+    (sources, inputs, opts)
+}
+
+fn run_jobs(
+    jobs: Vec<Job>,
+    dims: Vector3,
+    res: Vector3,
+    tx: Sender<Message>,
+    cancelled: Arc<AtomicBool>,
+) {
+    let mut outputs = HashMap::<Index, Arc<NodeOutput>>::new();
+
+    for job in jobs {
+        if cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let inputs = job.inputs.into_iter().map(|x| match x {
+            Input::Unplugged => None,
+            Input::Cached(x) => Some(x),
+            Input::Job(x) => outputs.get(&x).cloned(),
+        });
+
+        let (output, grid) = evaluate(&job.snapshot, inputs, dims, res);
+        let output = Arc::new(output);
+        outputs.insert(job.index, output.clone());
+
+        // the evaluator is gone
+        if tx.send((job.index, output, grid)).is_err() {
+            return;
+        }
+    }
+}
+
+// Output of the widget of `snapshot` given the outputs linked to its input
+// plugs, from the top one to the bottom one, along with the grid it kept.
+pub fn evaluate(
+    snapshot: &WidgetSnapshot,
+    mut inputs: impl Iterator<Item = Option<Arc<NodeOutput>>>,
+    dims: Vector3,
+    res: Vector3,
+) -> (NodeOutput, Vec<Vec<f64>>) {
+    // the voxel map and the globals start blank for the entry point
+    let mut voxmap = VoxelMap::new(dims, res);
+    let mut globals = Globals::default();
+
+    if let Some(input) = inputs.next().flatten() {
+        input.write(&mut voxmap, &mut globals);
+    }
+    globals.inputs = inputs.map(|x| x.map(|x| x.values.clone())).collect();
+
+    let mut grid = Vec::new();
+    if let Some(mut widget) = snapshot.restore() {
+        if let Some(widget) = widget.as_widget_plugable_mut() {
+            widget.apply(&mut voxmap, &mut globals);
+            grid = widget.take_grid();
+        }
+    }
+
+    (NodeOutput::read(&voxmap, &globals), grid)
+}
+
+pub fn entry_point(objmap: &ObjMap) -> Result<Index, String> {
+    let start: Vec<Index> = objmap
+        .objs
+        .data()
+        .filter(|(_, widget)| {
+            widget.get_type() == WidgetType::Io
+                && widget.as_widget_plugable().map_or(false, |x| {
+                    x.outputs().count() != 0 && x.inputs().count() == 0
+                })
+        })
+        .map(|(x, _)| *x)
+        .collect();
+
+    match start[..] {
+        [] => Err(String::from("Couldn't find an entry point")),
+        [start] => Ok(start),
+        _ => Err(String::from("Cannot handle multiple entry point")),
+    }
+}
+
+// Nodes in the order they are applied, from the entry point to the one
+// giving the output of the graph. A node comes once all of its inputs did.
+pub fn evaluation_order(objmap: &ObjMap) -> Result<Vec<Index>, String> {
+    let start = entry_point(objmap)?;
+    let count = objmap.objs.order();
+
+    if count == 1 {
+        return Err(String::from("Missing Output"));
+    }
+
+    let mut stack = vec![start];
+    let mut res = vec![start];
+
+    'dfs: while let Some(elt) = stack.pop() {
+        let prev = objmap.get_prev_widgets(elt).unwrap_or_default();
+
+        if !prev.iter().all(|x| res.contains(x)) {
+            continue;
+        }
+
+        for adj in objmap.get_next_widgets(elt).unwrap_or_default() {
+            if !res.contains(&adj) {
+                stack.push(adj);
+            }
+        }
+
+        // the entry point, or a node reached through several of its inputs
+        if res.contains(&elt) {
+            continue;
+        }
+
+        match objmap.objs.data_get(&elt).map(|x| x.get_type()) {
+            Some(WidgetType::Io) => {
+                res.push(elt);
+                break 'dfs;
+            }
+            Some(WidgetType::None) | None => {}
+            Some(_) => res.push(elt),
+        }
+    }
+
+    if res.len() < count {
+        return Err(String::from("Some plugs aren't connected"));
+    }
+
+    Ok(res)
+}
@@ -0,0 +1,414 @@
+// Undo/redo of the node editor. Every change made to the ObjMap is recorded
+// as a command that can be applied again or reverted. Widgets are found
+// back by their position, which is the key of the ObjMap.
+
+use crate::maths::Vec2u;
+use crate::widgets::analysis::*;
+use crate::widgets::coloration::*;
+use crate::widgets::dsquare::*;
+use crate::widgets::group::*;
+use crate::widgets::map::*;
+use crate::widgets::mask::*;
+use crate::widgets::perlin::*;
+use crate::widgets::roads::*;
+use crate::widgets::settlements::*;
+use crate::widgets::widget_io::*;
+use crate::widgets::*;
+
+use raylib::prelude::*;
+use std::collections::HashMap;
+
+// number of commands that can be undone
+const HISTORY_SIZE: usize = 128;
+
+// (output plug, input plug)
+pub type Link = (Vector2, Vector2);
+
+pub fn new_widget(kind: WidgetType) -> Option<Box<dyn Widget>> {
+    match kind {
+        WidgetType::Io => Some(Box::new(WidgetIO::default())),
+        WidgetType::Perlin => Some(Box::new(Perlin::default())),
+        WidgetType::Dsquare => Some(Box::new(Dsquare::default())),
+        WidgetType::Coloration => Some(Box::new(Coloration::default())),
+        WidgetType::Mask => Some(Box::new(Mask::default())),
+        WidgetType::Analysis => Some(Box::new(Analysis::default())),
+        WidgetType::Group => Some(Box::new(Group::default())),
+        WidgetType::Settlements => Some(Box::new(Settlements::default())),
+        WidgetType::Roads => Some(Box::new(Roads::default())),
+        WidgetType::None => None,
+    }
+}
+
+// what is needed to build a widget again, its links are kept apart
+#[derive(Clone)]
+pub struct WidgetSnapshot {
+    pub kind: WidgetType,
+    pub id: String,
+    pub rect: Rectangle,
+    pub plugs: PlugInterface,
+    pub opts: WidgetOpts,
+}
+
+impl WidgetSnapshot {
+    pub fn take(widget: &dyn Widget) -> Option<Self> {
+        let plugable = widget.as_widget_plugable()?;
+        let mut plugs = plugable.get_iplug().clone();
+
+        plugs.inputs.values_mut().for_each(|x| x.clear());
+        plugs.outputs.values_mut().for_each(|x| x.clear());
+
+        Some(Self {
+            kind: widget.get_type(),
+            id: widget.get_id(),
+            rect: plugable.get_rect(),
+            plugs,
+            opts: widget
+                .as_widget_configurable()
+                .map_or(WidgetOpts::None, |x| x.get_opts()),
+        })
+    }
+
+    pub fn pos(&self) -> Vector2 {
+        Vector2::new(self.rect.x, self.rect.y)
+    }
+
+    pub fn restore(&self) -> Option<Box<dyn Widget>> {
+        let mut widget = new_widget(self.kind)?;
+        widget.set_id(self.id.clone());
+
+        // the plugs are replaced below, the IoSwap signal can be ignored
+        if let Some(w) = widget.as_widget_configurable_mut() {
+            w.set_opts(self.opts.clone());
+        }
+
+        let plugable = widget.as_widget_plugable_mut()?;
+        plugable.set_rect(self.rect);
+        *plugable.get_iplug_mut() = self.plugs.clone();
+
+        Some(widget)
+    }
+}
+
+pub enum Command {
+    Create(Vec<WidgetSnapshot>, Vec<Link>),
+    Delete(Vec<WidgetSnapshot>, Vec<Link>),
+    Move {
+        from: Vector2,
+        to: Vector2,
+    },
+    // the new link and the ones it replaced
+    Link(Link, Vec<Link>),
+    Unlink(Vec<Link>),
+    // `links` were lost with the plugs of an IoSwap
+    Options {
+        pos: Vector2,
+        before: WidgetOpts,
+        after: WidgetOpts,
+        links: Vec<Link>,
+    },
+    // applied in order, reverted in reverse order
+    Batch(Vec<Command>),
+}
+
+// top left corner of the widgets
+pub fn anchor(snapshots: &[WidgetSnapshot]) -> Option<Vector2> {
+    snapshots
+        .iter()
+        .map(|x| x.pos())
+        .reduce(|a, b| Vector2::new(a.x.min(b.x), a.y.min(b.y)))
+}
+
+// Copies of the snapshots moved by `offset`, with where each of their plugs
+// went. The widgets move their own plugs so that the keys are the ones a
+// later move expects.
+pub fn translate_snapshots(
+    snapshots: &[WidgetSnapshot],
+    offset: Vector2,
+) -> Option<(Vec<WidgetSnapshot>, HashMap<Vec2u, Vector2>)> {
+    let mut plugs = HashMap::<Vec2u, Vector2>::new();
+    let mut res = Vec::with_capacity(snapshots.len());
+
+    for snapshot in snapshots.iter() {
+        let mut widget = snapshot.restore()?;
+        let plugable = widget.as_widget_plugable_mut()?;
+        plugable.translate_wplugs(offset);
+
+        let old_plugs = snapshot.plugs.inputs.keys();
+        for old in old_plugs.chain(snapshot.plugs.outputs.keys()) {
+            let old_pos: Vector2 = (*old).into();
+            let target = old_pos + offset;
+            let new = plugable
+                .plug_pos()
+                .map(|x| -> Vector2 { (*x).into() })
+                .min_by(|a, b| {
+                    (*a - target).length().total_cmp(&(*b - target).length())
+                });
+
+            if let Some(new) = new {
+                plugs.insert(*old, new);
+            }
+        }
+
+        res.push(WidgetSnapshot::take(widget.as_ref())?);
+    }
+
+    Some((res, plugs))
+}
+
+// ids following the ones given by `create`
+pub fn fresh_ids(objmap: &ObjMap, snapshots: &mut [WidgetSnapshot]) {
+    let count = objmap.objs.unique_keys_count() as usize;
+
+    snapshots.iter_mut().enumerate().for_each(|(i, x)| {
+        if let Some(widget) = new_widget(x.kind) {
+            x.id = format!("{}_{}", widget.get_id(), count + i);
+        }
+    });
+}
+
+fn relink(objmap: &mut ObjMap, links: &[Link]) {
+    links.iter().for_each(|(x, y)| {
+        objmap.link_widgets(*x, *y);
+    });
+}
+
+fn insert_widgets(
+    objmap: &mut ObjMap,
+    snapshots: &[WidgetSnapshot],
+    links: &[Link],
+) {
+    snapshots.iter().for_each(|x| {
+        if let Some(widget) = x.restore() {
+            objmap.insert_widget(x.pos(), widget);
+        }
+    });
+    relink(objmap, links);
+}
+
+fn remove_widgets(objmap: &mut ObjMap, snapshots: &[WidgetSnapshot]) {
+    snapshots.iter().for_each(|x| {
+        objmap.remove_widget(x.pos());
+    });
+}
+
+impl Command {
+    fn apply(&self, objmap: &mut ObjMap) {
+        match self {
+            Command::Create(snapshots, links) => {
+                insert_widgets(objmap, snapshots, links)
+            }
+            Command::Delete(snapshots, _) => remove_widgets(objmap, snapshots),
+            Command::Move { from, to } => {
+                if objmap.objs.contains_key(&(*from).into()) {
+                    objmap.move_widget_by(*from, *to - *from);
+                }
+            }
+            Command::Link((x, y), _) => {
+                objmap.link_widgets(*x, *y);
+            }
+            Command::Unlink(links) => {
+                links
+                    .iter()
+                    .for_each(|(x, y)| objmap.unlink_widgets(*x, *y));
+            }
+            Command::Options { pos, after, .. } => {
+                objmap.set_widget_opts(*pos, after.clone());
+            }
+            Command::Batch(commands) => {
+                commands.iter().for_each(|x| x.apply(objmap))
+            }
+        }
+    }
+
+    fn revert(&self, objmap: &mut ObjMap) {
+        match self {
+            Command::Create(snapshots, _) => remove_widgets(objmap, snapshots),
+            Command::Delete(snapshots, links) => {
+                insert_widgets(objmap, snapshots, links)
+            }
+            Command::Move { from, to } => {
+                if objmap.objs.contains_key(&(*to).into()) {
+                    objmap.move_widget_by(*to, *from - *to);
+                }
+            }
+            Command::Link((x, y), replaced) => {
+                objmap.unlink_widgets(*x, *y);
+                relink(objmap, replaced);
+            }
+            Command::Unlink(links) => relink(objmap, links),
+            Command::Options {
+                pos, before, links, ..
+            } => {
+                objmap.set_widget_opts(*pos, before.clone());
+                relink(objmap, links);
+            }
+            Command::Batch(commands) => {
+                commands.iter().rev().for_each(|x| x.revert(objmap))
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+    // option edits of the same widget are merged until the next click
+    merging: bool,
+    // position of the widget being dragged when the drag started
+    moving: Option<Vector2>,
+}
+
+impl History {
+    pub fn run(&mut self, command: Command, objmap: &mut ObjMap) {
+        command.apply(objmap);
+        self.push(command);
+    }
+
+    pub fn push(&mut self, command: Command) {
+        self.redo.clear();
+        self.undo.push(command);
+        self.merging = false;
+
+        if self.undo.len() > HISTORY_SIZE {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn record_opts(
+        &mut self,
+        pos: Vector2,
+        before: WidgetOpts,
+        after: WidgetOpts,
+        links: Vec<Link>,
+    ) {
+        if self.merging {
+            if let Some(Command::Options {
+                pos: last_pos,
+                after: last_after,
+                links: last_links,
+                ..
+            }) = self.undo.last_mut()
+            {
+                if *last_pos == pos {
+                    *last_after = after;
+                    last_links.extend(links);
+                    return;
+                }
+            }
+        }
+
+        self.push(Command::Options {
+            pos,
+            before,
+            after,
+            links,
+        });
+        self.merging = true;
+    }
+
+    pub fn close(&mut self) {
+        self.merging = false;
+    }
+
+    pub fn begin_move(&mut self, pos: Vector2) {
+        if self.moving.is_none() {
+            self.moving = Some(pos);
+        }
+    }
+
+    pub fn end_move(&mut self, pos: Vector2) {
+        if let Some(from) = self.moving.take() {
+            if from != pos {
+                self.push(Command::Move { from, to: pos });
+            }
+        }
+    }
+
+    pub fn undo(&mut self, objmap: &mut ObjMap) -> bool {
+        self.moving = None;
+        self.merging = false;
+
+        match self.undo.pop() {
+            Some(command) => {
+                command.revert(objmap);
+                self.redo.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, objmap: &mut ObjMap) -> bool {
+        self.moving = None;
+        self.merging = false;
+
+        match self.redo.pop() {
+            Some(command) => {
+                command.apply(objmap);
+                self.undo.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// widgets copied with the links between them
+#[derive(Default)]
+pub struct Clipboard {
+    pub widgets: Vec<WidgetSnapshot>,
+    pub links: Vec<Link>,
+}
+
+impl Clipboard {
+    pub fn copy(objmap: &ObjMap, positions: &[Vector2]) -> Self {
+        let widgets: Vec<WidgetSnapshot> = positions
+            .iter()
+            .filter_map(|x| objmap.objs.get(&(*x).into()))
+            .filter_map(|x| WidgetSnapshot::take(x.as_ref()))
+            .collect();
+
+        let plugs: Vec<Vec2u> = widgets
+            .iter()
+            .flat_map(|x| x.plugs.inputs.keys().chain(x.plugs.outputs.keys()))
+            .copied()
+            .collect();
+
+        let mut links = Vec::new();
+        widgets.iter().for_each(|x| {
+            objmap.widget_links(x.pos()).into_iter().for_each(|(a, b)| {
+                if plugs.contains(&a.into())
+                    && plugs.contains(&b.into())
+                    && !links.contains(&(a, b))
+                {
+                    links.push((a, b));
+                }
+            })
+        });
+
+        Self { widgets, links }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.widgets.is_empty()
+    }
+
+    // copies of the widgets with fresh ids, moved so that the top left one
+    // lands on `pos`
+    pub fn paste(&self, objmap: &ObjMap, pos: Vector2) -> Option<Command> {
+        let offset = pos - anchor(&self.widgets)?;
+        let (mut widgets, plugs) = translate_snapshots(&self.widgets, offset)?;
+        fresh_ids(objmap, &mut widgets);
+
+        let links = self
+            .links
+            .iter()
+            .filter_map(|(a, b)| {
+                Some((*plugs.get(&(*a).into())?, *plugs.get(&(*b).into())?))
+            })
+            .collect();
+
+        Some(Command::Create(widgets, links))
+    }
+}
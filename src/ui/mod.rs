@@ -1,4 +1,6 @@
 pub mod camera;
+pub mod evaluator;
+pub mod history;
 pub mod maths;
 pub mod multimap;
 pub mod ui;
@@ -1,14 +1,28 @@
 use crate::display::build_2D::*;
+use crate::display::settlements::{Road, Settlement, SettlementKind};
+use crate::evaluator::*;
+use crate::history::*;
 use crate::maths::*;
 use crate::multimap::*;
 // use crate::perlin_noise;
 // use crate::voxels::*;
-use crate::obj_export::export_obj::create_3d_terrain;
+use crate::obj_export::export_obj::{
+    create_3d_scene, create_chunked_terrain, create_printable_terrain,
+    create_simplified_3d_terrain, ChunkOpts, SceneOpts,
+};
+use crate::obj_export::geojson::geojson::export_geojson;
+use crate::widgets::analysis::*;
 use crate::widgets::coloration::*;
 use crate::widgets::dsquare::*;
+use crate::widgets::group::*;
 use crate::widgets::map::*;
+use crate::widgets::mask::*;
+use crate::widgets::number_box::*;
 use crate::widgets::perlin::*;
 use crate::widgets::previewer::*;
+use crate::widgets::roads::*;
+use crate::widgets::settlements::*;
+use crate::widgets::viewer::*;
 use crate::widgets::widget_chooser::*;
 use crate::widgets::widget_io::*;
 use crate::widgets::*;
@@ -41,6 +55,19 @@ pub const MOUSE_OVERLAY: Color = Color {
     a: 128,
 };
 pub const WINDOW_BOX_TITLE_SIZE: f32 = 23.0;
+pub const EXPORT_MAX_HEIGHT: f64 = 256.0;
+// maximum vertical error of simplified 3D exports, altitude 1.0 is
+// EXPORT_MAX_HEIGHT
+pub const EXPORT_TOLERANCE: f64 = 1.0;
+// faces of the 3D scene and tile exports meeting at a sharper angle, in
+// degrees, don't share their normals. None keeps the whole terrain smooth
+pub const EXPORT_HARD_EDGE_ANGLE: Option<f64> = None;
+// vertical exaggeration and base thickness of printable STL exports
+pub const EXPORT_EXAGGERATION: f64 = 2.0;
+pub const EXPORT_BASE_THICKNESS: f64 = 8.0;
+// font size of the settlement names in PNG exports
+pub const LABEL_CITY_SIZE: i32 = 12;
+pub const LABEL_VILLAGE_SIZE: i32 = 10;
 
 // s for static; r for relative
 // by definition static's zoom should be 1.0
@@ -65,11 +92,22 @@ pub struct Globals {
     pub r_mouse_delta: Vector2,
     pub origin: Vector2,
     pub altmap: Vec<Vec<f64>>,
+    // thresholds of the last coloration, the water of the exports is cut
+    // with them
+    pub biome_opts: BiomeOpts,
+    // values of the input plugs after the first one, one per voxel, `None`
+    // for the unplugged ones
+    pub inputs: Vec<Option<Vec<f64>>>,
+    // placed by the settlements and roads nodes, in grid coordinates
+    pub settlements: Vec<Settlement>,
+    pub roads: Vec<Road>,
     pub selection: Vec<Rectangle>,
     pub capture: [Option<Capture>; 2],
     pub capture_anchor: Option<Vector2>,
     pub is_selection_moving: bool,
     pub configuring: Option<Vector2>,
+    // number box of the configure panel being typed in
+    pub text_edit: Option<TextEdit>,
 }
 
 impl Default for Globals {
@@ -88,11 +126,16 @@ impl Default for Globals {
             r_mouse_delta: Vector2::default(),
             origin: Vector2::default(),
             altmap: Vec::new(),
+            biome_opts: BiomeOpts::default(),
+            inputs: Vec::new(),
+            settlements: Vec::new(),
+            roads: Vec::new(),
             selection: Vec::new(),
             capture_anchor: None,
             capture: [None, None],
             is_selection_moving: false,
             configuring: None,
+            text_edit: None,
         }
     }
 }
@@ -101,6 +144,7 @@ pub struct StaticWidgets {
     pub objmap: ObjMap,
     pub chooser: WidgetChooser,
     pub previewer: Previewer,
+    pub viewer: Viewer,
 }
 
 impl Default for StaticWidgets {
@@ -109,6 +153,7 @@ impl Default for StaticWidgets {
             objmap: ObjMap::new(String::from("ui_map")),
             chooser: WidgetChooser::default(),
             previewer: Previewer::default(),
+            viewer: Viewer::default(),
         }
     }
 }
@@ -117,13 +162,28 @@ impl Default for StaticWidgets {
 pub struct UI {
     globals: Globals,
     widgets: StaticWidgets,
+    history: History,
+    clipboard: Clipboard,
+    evaluator: Evaluator,
+    // stamp of the output shown by the thumbnail of every node
+    thumbnails: HashMap<Index, u64>,
 }
 
 impl UI {
+    fn viewer_keycalls(&mut self, dhandle: &mut RaylibDrawHandle) {
+        if dhandle.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+            self.set_exit_status();
+        } else if dhandle.is_key_pressed(KeyboardKey::KEY_F9) {
+            self.widgets.viewer.visible = false;
+        }
+    }
+
     fn keycalls(&mut self, dhandle: &mut RaylibDrawHandle) {
         let wheel = dhandle.get_mouse_wheel_move();
 
-        if dhandle.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+        let typing = self.globals.text_edit.is_some();
+
+        if !typing && dhandle.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
             self.set_exit_status();
         }
 
@@ -143,7 +203,7 @@ impl UI {
 
                 if let Some(plug_pos) = plug {
                     self.globals.rclick_locked = true;
-                    self.widgets.objmap.remove_links(plug_pos)
+                    self.remove_links(plug_pos);
                 } else if self.globals.capture[0].is_none() {
                     self.globals.configuring = None;
                     let mut delta = self.globals.s_mouse_delta;
@@ -155,7 +215,9 @@ impl UI {
             self.globals.rclick_locked = false;
         }
 
-        if dhandle.is_key_down(KeyboardKey::KEY_LEFT) {
+        if typing {
+            // the keys go to the number box being typed in
+        } else if dhandle.is_key_down(KeyboardKey::KEY_LEFT) {
             let mut delta = Vector2::new(10.0, 0.0);
             delta.scale(-1.0 / self.globals.r_camera.zoom);
             self.globals.r_camera.target += delta;
@@ -174,12 +236,31 @@ impl UI {
         } else if dhandle.is_key_pressed(KeyboardKey::KEY_F1) {
             self.reset_configuring();
             self.widgets.chooser.visible ^= true;
+
+            if self.widgets.chooser.visible {
+                self.widgets.chooser.set_library(load_library());
+            }
         } else if dhandle.is_key_pressed(KeyboardKey::KEY_F2) {
             self.widgets.previewer.visible ^= true;
         } else if dhandle.is_key_pressed(KeyboardKey::KEY_F3) {
-            self.export_image();
+            if dhandle.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                self.export_vector();
+            } else {
+                self.export_image();
+            }
         } else if dhandle.is_key_pressed(KeyboardKey::KEY_F4) {
-            self.export_object();
+            let simplified = dhandle.is_key_down(KeyboardKey::KEY_LEFT_SHIFT);
+            self.export_object(simplified);
+        } else if dhandle.is_key_pressed(KeyboardKey::KEY_F7) {
+            let chunked = dhandle.is_key_down(KeyboardKey::KEY_LEFT_SHIFT);
+            self.export_gltf(chunked);
+        } else if dhandle.is_key_pressed(KeyboardKey::KEY_F8) {
+            self.export_stl();
+        } else if dhandle.is_key_pressed(KeyboardKey::KEY_F9) {
+            self.widgets.viewer.visible ^= true;
+        } else if dhandle.is_key_pressed(KeyboardKey::KEY_F6) {
+            self.widgets.previewer.shaded ^= true;
+            self.widgets.previewer.shading_changed = true;
         } else if dhandle.is_key_pressed(KeyboardKey::KEY_ONE) {
             self.widgets.chooser.selected_index = 1;
         } else if dhandle.is_key_pressed(KeyboardKey::KEY_TWO) {
@@ -188,6 +269,14 @@ impl UI {
             self.widgets.chooser.selected_index = 3;
         } else if dhandle.is_key_pressed(KeyboardKey::KEY_FOUR) {
             self.widgets.chooser.selected_index = 4;
+        } else if dhandle.is_key_pressed(KeyboardKey::KEY_FIVE) {
+            self.widgets.chooser.selected_index = 5;
+        } else if dhandle.is_key_pressed(KeyboardKey::KEY_SIX) {
+            self.widgets.chooser.selected_index = 6;
+        } else if dhandle.is_key_pressed(KeyboardKey::KEY_SEVEN) {
+            self.widgets.chooser.selected_index = 7;
+        } else if dhandle.is_key_pressed(KeyboardKey::KEY_EIGHT) {
+            self.widgets.chooser.selected_index = 8;
         } else if dhandle.is_key_pressed(KeyboardKey::KEY_F10) {
             self.widgets.objmap.dump();
         } else if dhandle.is_key_pressed(KeyboardKey::KEY_F11) {
@@ -209,22 +298,39 @@ impl UI {
                         .unwrap()
                 );
             }
-        } else if dhandle.is_key_pressed(KeyboardKey::KEY_C) {
+        } else if dhandle.is_key_pressed(KeyboardKey::KEY_C)
+            && dhandle.is_key_up(KeyboardKey::KEY_LEFT_CONTROL)
+        {
             if let Some(rect) = self.globals.selection.last() {
                 let pos = Vector2::new(
                     rect.x + SELECTION_OFFSET,
                     rect.y + SELECTION_OFFSET,
                 );
-                self.widgets.objmap.remove_widget_links(pos)
+                let links = self.widgets.objmap.widget_links(pos);
+                self.widgets.objmap.remove_widget_links(pos);
+
+                if !links.is_empty() {
+                    self.history.push(Command::Unlink(links));
+                }
             }
         } else if dhandle.is_key_pressed(KeyboardKey::KEY_F5) {
             self.run_generation()
+        } else if dhandle.is_key_pressed(KeyboardKey::KEY_F12) {
+            self.evaluator.live = !self.evaluator.live;
+            println!(
+                "Live evaluation {}",
+                if self.evaluator.live { "ON" } else { "OFF" }
+            );
         }
 
         if wheel != 0.0 {
             self.scale_camera_zoom(wheel);
         }
 
+        if dhandle.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON) {
+            self.history.close();
+        }
+
         if dhandle.is_mouse_button_down(MouseButton::MOUSE_LEFT_BUTTON) {
             if !self.globals.lclick_locked {
                 self.lock_click();
@@ -299,11 +405,18 @@ impl UI {
                     self.globals.configuring = Some(self.globals.r_mouse);
                 } else {
                     self.globals.is_selection_moving = true;
+                    self.history.begin_move(pos);
                 }
             }
         } else if dhandle.is_mouse_button_up(MouseButton::MOUSE_LEFT_BUTTON) {
             if self.globals.lclick_locked {
                 self.unlock_click();
+
+                if let Some(Capture::Rectangle(rect)) = self.globals.capture[0]
+                {
+                    self.history.end_move(Vector2::new(rect.x, rect.y));
+                }
+
                 self.globals.is_selection_moving = false;
                 self.link_mouse_target();
                 self.reset_capture();
@@ -312,65 +425,186 @@ impl UI {
             ()
         }
 
+        if typing {
+            return;
+        }
+
         if dhandle.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
             && dhandle.is_key_pressed(KeyboardKey::KEY_R)
         {
             self.reset_workspace();
         }
 
+        if dhandle.is_key_down(KeyboardKey::KEY_LEFT_CONTROL) {
+            if dhandle.is_key_pressed(KeyboardKey::KEY_C) {
+                self.copy_selection();
+            } else if dhandle.is_key_pressed(KeyboardKey::KEY_V) {
+                self.paste();
+            } else if dhandle.is_key_pressed(KeyboardKey::KEY_D) {
+                self.duplicate_selection();
+            } else if dhandle.is_key_pressed(KeyboardKey::KEY_G) {
+                if dhandle.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                    self.ungroup_selection();
+                } else {
+                    self.group_selection();
+                }
+            }
+        }
+
+        if dhandle.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
+            && dhandle.is_key_pressed(KeyboardKey::KEY_Z)
+        {
+            if dhandle.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                self.redo();
+            } else {
+                self.undo();
+            }
+        }
+
         if dhandle.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
             self.delete_selection();
         }
     }
 
-    fn export_object(&self) {
+    // textured with the biome map from `export_image`
+    fn export_object(&self, simplified: bool) {
+        self.export_image();
+
+        let dims = self.widgets.previewer.voxmap.dims;
+        let res = if simplified {
+            create_simplified_3d_terrain(
+                dims.x as usize,
+                dims.y as usize,
+                self.globals.altmap.clone(),
+                "./output_3d.obj",
+                EXPORT_MAX_HEIGHT,
+                EXPORT_TOLERANCE,
+                Some("output.png"),
+            )
+        } else {
+            create_3d_scene(
+                dims.x as usize,
+                dims.y as usize,
+                self.globals.altmap.clone(),
+                "./output_3d.obj",
+                EXPORT_MAX_HEIGHT,
+                Some("output.png"),
+                &self.scene_opts(),
+            )
+        };
+
+        match res {
+            Ok(_) => println!("Exporting to 3D... DONE"),
+            Err(e) => println!("Exporting to 3D... {e}"),
+        }
+    }
+
+    // binary glTF with the biome map embedded as its texture, split in
+    // tiles and levels of detail when `chunked`
+    fn export_gltf(&self, chunked: bool) {
+        self.export_image();
+
         let dims = self.widgets.previewer.voxmap.dims;
-        let _ = create_3d_terrain(
+        let res = if chunked {
+            create_chunked_terrain(
+                dims.x as usize,
+                dims.y as usize,
+                self.globals.altmap.clone(),
+                "./output_chunks/terrain.glb",
+                EXPORT_MAX_HEIGHT,
+                Some("../output.png"),
+                &ChunkOpts {
+                    hard_edge_angle: EXPORT_HARD_EDGE_ANGLE,
+                    ..ChunkOpts::default()
+                },
+            )
+        } else {
+            create_3d_scene(
+                dims.x as usize,
+                dims.y as usize,
+                self.globals.altmap.clone(),
+                "./output_3d.glb",
+                EXPORT_MAX_HEIGHT,
+                Some("output.png"),
+                &self.scene_opts(),
+            )
+        };
+
+        match res {
+            Ok(_) => println!("Exporting to glTF... DONE"),
+            Err(e) => println!("Exporting to glTF... {e}"),
+        }
+    }
+
+    // the water biomes become their own object at sea level
+    fn scene_opts(&self) -> SceneOpts {
+        SceneOpts {
+            water: Some((
+                water_mask(&self.globals.altmap, &self.globals.biome_opts),
+                self.globals.biome_opts.deep_water,
+            )),
+            skirt_depth: None,
+            hard_edge_angle: EXPORT_HARD_EDGE_ANGLE,
+        }
+    }
+
+    fn export_stl(&self) {
+        let dims = self.widgets.previewer.voxmap.dims;
+        match create_printable_terrain(
             dims.x as usize,
             dims.y as usize,
             self.globals.altmap.clone(),
-            "./output_3d.obj",
-            256.0,
-        );
-
-        println!("Exporting to 3D... DONE");
+            "./output_3d.stl",
+            EXPORT_MAX_HEIGHT,
+            EXPORT_EXAGGERATION,
+            EXPORT_BASE_THICKNESS,
+        ) {
+            Ok(_) => println!("Exporting to STL... DONE"),
+            Err(e) => println!("Exporting to STL... {e}"),
+        }
     }
 
     fn export_image(&self) {
-        let dims = self.widgets.previewer.voxmap.dims;
-
+        let previewer = &self.widgets.previewer;
         let mut image =
-            Image::gen_image_color(dims.x as i32, dims.y as i32, Color::WHITE);
-
-        self.widgets.previewer.voxmap.voxels.iter().for_each(|x| {
-            let color = match x.value {
-                -2.0 => Color::BLUE,
-                -1.0 => Color::DARKBLUE,
-                2.0 => Color::GOLD,
-                3.0 => Color::GRAY,
-                4.0 => Color::LIGHTGRAY,
-                5.0 => Color::WHITE,
-                6.0 => Color::YELLOW,
-                7.0 => Color::DARKGREEN,
-                8.0 => Color::PURPLE,
-                -5.0 => Color::BLACK,
-                10.0 => Color::RED,
-                11.0 => Color::ORANGE,
-                12.0 => Color::BLACK,
-                _ if x.value >= 0.0 && x.value < DARK_FOREST_THRESHOLD => {
-                    Color::GREEN
-                }
-                _ if x.value >= DARK_FOREST_THRESHOLD => Color::DARKGREEN,
-                _ => Color::DARKGREEN,
+            previewer.voxmap.render_to_img(previewer.light.as_ref());
+        let res = previewer.voxmap.res;
+
+        // names of the settlements on the right of their cross
+        for settlement in self.globals.settlements.iter() {
+            let size = match settlement.kind {
+                SettlementKind::City => LABEL_CITY_SIZE,
+                SettlementKind::Village => LABEL_VILLAGE_SIZE,
             };
-            image.draw_pixel(x.coords.x as i32, x.coords.y as i32, color);
-        });
+
+            image.draw_text(
+                &settlement.name,
+                ((settlement.pos.0 as f32 + 4.0) * res.x) as i32,
+                (settlement.pos.1 as f32 * res.y) as i32 - size / 2,
+                size,
+                Color::BLACK,
+            );
+        }
 
         // export_image(&image, "carte.png");
         image.export_image("output.png");
         println!("Exporting to PNG... DONE");
     }
 
+    // settlements and roads of the last evaluation, in the cells of the image
+    fn export_vector(&self) {
+        let dims = self.widgets.previewer.voxmap.dims;
+        match export_geojson(
+            "./output.geojson",
+            &self.globals.settlements,
+            &self.globals.roads,
+            (dims.x as usize, dims.y as usize),
+        ) {
+            Ok(_) => println!("Exporting to GeoJSON... DONE"),
+            Err(e) => println!("Exporting to GeoJSON... {e}"),
+        }
+    }
+
     fn reset_chooser_idx(&mut self) {
         self.widgets.chooser.reset_index();
     }
@@ -416,6 +650,16 @@ impl UI {
         }
 
         // self.globals.plugmap.clear();
+        let positions: Vec<Vector2> = self
+            .widgets
+            .objmap
+            .objs
+            .data_values()
+            .filter_map(|x| x.as_widget_rectangle())
+            .map(|x| x.get_pos())
+            .collect();
+
+        self.remove_widgets(positions);
         self.widgets.objmap.objs.clear();
         self.reset_selection();
         self.reset_configuring();
@@ -448,19 +692,304 @@ impl UI {
             return;
         }
 
-        while let Some(rect) = self.globals.selection.pop() {
-            let pos = Vector2::new(
-                rect.x + SELECTION_OFFSET,
-                rect.y + SELECTION_OFFSET,
-            );
-
-            self.widgets.objmap.remove_widget(pos);
-        }
+        let positions = self.selection_positions();
+        self.globals.selection.clear();
 
+        self.remove_widgets(positions);
         self.globals.is_selection_moving = false;
         self.globals.configuring = None;
     }
 
+    fn selection_positions(&self) -> Vec<Vector2> {
+        self.globals
+            .selection
+            .iter()
+            .map(|rect| {
+                Vector2::new(
+                    rect.x + SELECTION_OFFSET,
+                    rect.y + SELECTION_OFFSET,
+                )
+            })
+            .collect()
+    }
+
+    fn copy_selection(&mut self) {
+        let positions = self.selection_positions();
+
+        if !positions.is_empty() {
+            self.clipboard = Clipboard::copy(&self.widgets.objmap, &positions);
+        }
+    }
+
+    fn paste(&mut self) {
+        if self.clipboard.is_empty() {
+            return;
+        }
+
+        let command = self
+            .clipboard
+            .paste(&self.widgets.objmap, self.globals.r_mouse);
+        self.insert_pasted(command);
+    }
+
+    // same as a copy and paste, the clipboard is kept
+    fn duplicate_selection(&mut self) {
+        let positions = self.selection_positions();
+        let command = Clipboard::copy(&self.widgets.objmap, &positions)
+            .paste(&self.widgets.objmap, self.globals.r_mouse);
+        self.insert_pasted(command);
+    }
+
+    // the pasted widgets become the selection, nothing is pasted if one of
+    // them would overlap an existing widget
+    fn insert_pasted(&mut self, command: Option<Command>) {
+        if self.widgets.objmap.locked {
+            return;
+        }
+
+        let rects: Vec<Rectangle> = match &command {
+            Some(Command::Create(snapshots, _)) => {
+                snapshots.iter().map(|x| selection_rect(x.rect)).collect()
+            }
+            _ => return,
+        };
+
+        if rects
+            .iter()
+            .any(|x| self.widgets.objmap.any_rect_colliding(*x))
+        {
+            return;
+        }
+
+        self.reset_selection();
+        self.reset_configuring();
+        self.globals.selection = rects;
+        self.history.run(command.unwrap(), &mut self.widgets.objmap);
+    }
+
+    // replaces the selection with a group exposing its dangling plugs
+    fn group_selection(&mut self) {
+        if self.widgets.objmap.locked {
+            return;
+        }
+
+        let positions = self.selection_positions();
+        let objmap = &self.widgets.objmap;
+
+        if positions.iter().any(|x| {
+            objmap
+                .objs
+                .get(&(*x).into())
+                .map_or(true, |w| w.get_type() == WidgetType::Io)
+        }) {
+            eprintln!("IO widgets can't be grouped");
+            return;
+        }
+
+        let inner = Clipboard::copy(objmap, &positions);
+        let mut links: Vec<Link> = Vec::new();
+        positions
+            .iter()
+            .flat_map(|x| objmap.widget_links(*x))
+            .for_each(|x| {
+                if !links.contains(&x) {
+                    links.push(x);
+                }
+            });
+        let external: Vec<Link> = links
+            .iter()
+            .filter(|x| !inner.links.contains(x))
+            .copied()
+            .collect();
+
+        let (opts, pos, plugs) = match GroupOpts::collapse(
+            &inner.widgets,
+            &inner.links,
+            &external,
+        ) {
+            Some(res) => res,
+            None => return,
+        };
+
+        // a group computes a single output
+        if opts.outputs.len() > 1 {
+            eprintln!("Groups can only have one dangling output");
+            return;
+        }
+
+        let reroute = |plug: Vector2| {
+            plugs
+                .get(&plug.into())
+                .and_then(|x| opts.exposed_plug(pos, *x))
+                .unwrap_or(plug)
+        };
+        let rerouted = external
+            .iter()
+            .map(|(x, y)| (reroute(*x), reroute(*y)))
+            .collect();
+
+        let group = Group::new(objmap, pos, opts);
+        let rect = selection_rect(group.get_rect());
+
+        if objmap.rect_colliding_except(rect, &positions) {
+            eprintln!("Not enough room for the group");
+            return;
+        }
+
+        let snapshot = match WidgetSnapshot::take(&group) {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+
+        self.reset_selection();
+        self.reset_configuring();
+        self.globals.selection.push(rect);
+        self.history.run(
+            Command::Batch(vec![
+                Command::Delete(inner.widgets, links),
+                Command::Create(vec![snapshot], rerouted),
+            ]),
+            &mut self.widgets.objmap,
+        );
+    }
+
+    fn ungroup_selection(&mut self) {
+        if let Some(pos) = self.selection_positions().last() {
+            self.ungroup(*pos);
+        }
+    }
+
+    // puts the sub-graph of the group back in its place
+    fn ungroup(&mut self, pos: Vector2) {
+        if self.widgets.objmap.locked {
+            return;
+        }
+
+        let objmap = &self.widgets.objmap;
+        let opts = match objmap.get_widget_opts(pos) {
+            WidgetOpts::Group(opts) => opts,
+            _ => return,
+        };
+        let group = objmap
+            .objs
+            .get(&pos.into())
+            .and_then(|x| WidgetSnapshot::take(x.as_ref()));
+        let (group, (mut widgets, mut links, plugs)) =
+            match (group, opts.expand(pos)) {
+                (Some(group), Some(res)) => (group, res),
+                _ => return,
+            };
+
+        fresh_ids(objmap, &mut widgets);
+        let rects: Vec<Rectangle> =
+            widgets.iter().map(|x| selection_rect(x.rect)).collect();
+
+        if rects
+            .iter()
+            .any(|x| objmap.rect_colliding_except(*x, &[pos]))
+        {
+            eprintln!("Not enough room to expand the group");
+            return;
+        }
+
+        let external = objmap.widget_links(pos);
+        let reroute = |plug: Vector2| {
+            opts.inner_plug(pos, plug)
+                .and_then(|x| plugs.get(&x.into()).copied())
+                .unwrap_or(plug)
+        };
+        external
+            .iter()
+            .for_each(|(x, y)| links.push((reroute(*x), reroute(*y))));
+
+        self.reset_selection();
+        self.reset_configuring();
+        self.globals.selection = rects;
+        self.history.run(
+            Command::Batch(vec![
+                Command::Delete(vec![group], external),
+                Command::Create(widgets, links),
+            ]),
+            &mut self.widgets.objmap,
+        );
+    }
+
+    fn save_to_library(&mut self, pos: Vector2) {
+        if let WidgetOpts::Group(opts) =
+            self.widgets.objmap.get_widget_opts(pos)
+        {
+            match save_group(&opts) {
+                Ok(path) => {
+                    println!("Saving to {}... DONE", path.display());
+                    self.widgets.chooser.set_library(load_library());
+                }
+                Err(e) => println!("Saving to the library... {e}"),
+            }
+        }
+    }
+
+    // removes the widgets as a single command of the history
+    fn remove_widgets(&mut self, positions: Vec<Vector2>) {
+        let mut snapshots = Vec::new();
+        let mut links: Vec<Link> = Vec::new();
+
+        for pos in positions {
+            self.widgets
+                .objmap
+                .widget_links(pos)
+                .into_iter()
+                .for_each(|x| {
+                    if !links.contains(&x) {
+                        links.push(x);
+                    }
+                });
+
+            if let Some(snapshot) = self
+                .widgets
+                .objmap
+                .remove_widget(pos)
+                .and_then(|w| WidgetSnapshot::take(w.as_ref()))
+            {
+                snapshots.push(snapshot);
+            }
+        }
+
+        if !snapshots.is_empty() {
+            self.history.push(Command::Delete(snapshots, links));
+        }
+    }
+
+    fn remove_links(&mut self, plug_pos: Vector2) {
+        let links = self.widgets.objmap.plug_links(plug_pos);
+        self.widgets.objmap.remove_links(plug_pos);
+
+        if !links.is_empty() {
+            self.history.push(Command::Unlink(links));
+        }
+    }
+
+    fn undo(&mut self) {
+        if self.widgets.objmap.locked {
+            return;
+        }
+
+        self.reset_selection();
+        self.reset_capture();
+        self.reset_configuring();
+        self.history.undo(&mut self.widgets.objmap);
+    }
+
+    fn redo(&mut self) {
+        if self.widgets.objmap.locked {
+            return;
+        }
+
+        self.reset_selection();
+        self.reset_capture();
+        self.reset_configuring();
+        self.history.redo(&mut self.widgets.objmap);
+    }
+
     fn link_mouse_target(&mut self) {
         let mouse_c = self.get_mouse_circle();
 
@@ -473,7 +1002,28 @@ impl UI {
                 self.reset_configuring();
 
                 if let Some(to) = col {
-                    self.widgets.objmap.link_widgets(from.pos, to);
+                    let objmap = &mut self.widgets.objmap;
+                    let links_of = |objmap: &ObjMap| {
+                        let mut res = objmap.plug_links(from.pos);
+                        res.extend(objmap.plug_links(to));
+                        res
+                    };
+
+                    let before = links_of(objmap);
+                    objmap.link_widgets(from.pos, to);
+                    let after = links_of(objmap);
+
+                    // a new input link replaces the previous one
+                    if let Some(&link) =
+                        after.iter().find(|x| !before.contains(x))
+                    {
+                        let replaced = before
+                            .into_iter()
+                            .filter(|x| !after.contains(x))
+                            .collect();
+
+                        self.history.push(Command::Link(link, replaced));
+                    }
                 }
             }
         }
@@ -485,9 +1035,12 @@ impl UI {
         handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
     ) {
         let circle = Circle::new(pos, SELECTION_OFFSET);
+        let pos = self.widgets.objmap.find_target(&circle).unwrap().get_pos();
+        let before = self.widgets.objmap.get_widget_opts(pos);
+        let links = self.widgets.objmap.widget_links(pos);
+
         let widget = self.widgets.objmap.find_target(&circle).unwrap();
         let mut sig = WidgetSignal::None;
-        let pos = widget.get_pos();
 
         if let Some(widget) = widget.as_widget_configurable_mut() {
             self.widgets.chooser.unready();
@@ -496,64 +1049,27 @@ impl UI {
 
         match sig {
             WidgetSignal::IoSwap(itoadd, otoadd, todelete) => {
-                todelete.into_iter().for_each(|x| {
-                    self.widgets.objmap.remove_links(x);
-                    self.widgets.objmap.objs.remove_alias(&x.into());
-                });
-
                 self.widgets
                     .objmap
-                    .objs
-                    .get_mut(&pos.into())
-                    .unwrap()
-                    .as_widget_plugable_mut()
-                    .unwrap()
-                    .get_iplug_mut()
-                    .inputs
-                    .clear();
-
-                self.widgets
-                    .objmap
-                    .objs
-                    .get_mut(&pos.into())
-                    .unwrap()
-                    .as_widget_plugable_mut()
-                    .unwrap()
-                    .get_iplug_mut()
-                    .outputs
-                    .clear();
-
-                itoadd.into_iter().for_each(|x| {
-                    self.widgets.objmap.objs.alias(&pos.into(), x.into());
-                    self.widgets
-                        .objmap
-                        .objs
-                        .get_mut(&pos.into())
-                        .unwrap()
-                        .as_widget_plugable_mut()
-                        .unwrap()
-                        .get_iplug_mut()
-                        .inputs
-                        .insert(x.into(), Vec::new());
-                });
-
-                otoadd.into_iter().for_each(|x| {
-                    self.widgets.objmap.objs.alias(&pos.into(), x.into());
-                    self.widgets
-                        .objmap
-                        .objs
-                        .get_mut(&pos.into())
-                        .unwrap()
-                        .as_widget_plugable_mut()
-                        .unwrap()
-                        .get_iplug_mut()
-                        .outputs
-                        .insert(x.into(), Vec::new());
-                })
+                    .swap_plugs(pos, itoadd, otoadd, todelete);
             }
+            WidgetSignal::SaveGroup => self.save_to_library(pos),
+            WidgetSignal::Ungroup => return self.ungroup(pos),
             WidgetSignal::None => {}
             _ => {}
         }
+
+        let after = self.widgets.objmap.get_widget_opts(pos);
+
+        if after != before {
+            let remaining = self.widgets.objmap.widget_links(pos);
+            let lost = links
+                .into_iter()
+                .filter(|x| !remaining.contains(x))
+                .collect();
+
+            self.history.record_opts(pos, before, after, lost);
+        }
     }
 
     fn capture_mouse_target(&mut self) {
@@ -622,6 +1138,32 @@ impl UI {
                 &self.widgets.objmap,
                 &mut self.globals,
             ))),
+            WidgetType::Mask => Some(Box::new(Mask::create(
+                &self.widgets.objmap,
+                &mut self.globals,
+            ))),
+            WidgetType::Analysis => Some(Box::new(Analysis::create(
+                &self.widgets.objmap,
+                &mut self.globals,
+            ))),
+            WidgetType::Settlements => Some(Box::new(Settlements::create(
+                &self.widgets.objmap,
+                &mut self.globals,
+            ))),
+            WidgetType::Roads => Some(Box::new(Roads::create(
+                &self.widgets.objmap,
+                &mut self.globals,
+            ))),
+            WidgetType::Group => {
+                let opts = self.widgets.chooser.get_group()?.clone();
+                let group = Group::create(
+                    &self.widgets.objmap,
+                    &mut self.globals,
+                    opts,
+                );
+
+                Some(Box::new(group))
+            }
             WidgetType::None => None,
         }
     }
@@ -645,22 +1187,20 @@ impl UI {
                     );
 
                     if !self.widgets.objmap.any_rect_colliding(rect) {
-                        let pos: Vec2u = self.globals.r_mouse.into();
+                        let pos = self.globals.r_mouse;
 
-                        let plugs_to_alias: Option<Vec<Vec2u>> = widget
-                            .as_widget_plugable()
-                            .map(|pl| pl.plug_pos().copied().collect());
-
-                        self.widgets.objmap.objs.insert(pos, widget);
-
-                        if let Some(vec) = plugs_to_alias {
-                            vec.into_iter().for_each(|x| {
-                                self.widgets.objmap.objs.alias(&pos, x);
-                            });
+                        if let Some(snapshot) =
+                            WidgetSnapshot::take(widget.as_ref())
+                        {
+                            self.history.push(Command::Create(
+                                vec![snapshot],
+                                Vec::new(),
+                            ));
                         }
 
+                        self.widgets.objmap.insert_widget(pos, widget);
                         self.globals.selection.push(rect);
-                        self.globals.configuring = Some(pos.into());
+                        self.globals.configuring = Some(pos);
                     }
                 }
             }
@@ -671,133 +1211,65 @@ impl UI {
         }
     }
 
-    pub fn dfs_traversal(&mut self, start: Index) -> Option<String> {
-        let mut stack: Vec<Index> = Vec::new();
-        let order = self.widgets.objmap.objs.order();
-
-        if order == 1 {
-            return Some(String::from("Missing Output"));
-        }
-
-        let mut mark = HashMap::<Index, bool>::with_capacity(order);
-
-        mark.insert(start, true);
-        stack.push(start);
-
-        'dfs: while let Some(elt) = stack.pop() {
-            if self
-                .widgets
-                .objmap
-                .get_prev_widgets(elt)
-                .unwrap()
-                .iter()
-                .all(|x| {
-                    let t = mark.get(x);
-                    t.is_some() && *t.unwrap()
-                })
+    // thumbnails of the nodes whose output changed since the last call
+    pub fn update_thumbnails(
+        &mut self,
+        handle: &mut RaylibHandle,
+        rl_thread: &RaylibThread,
+    ) {
+        let thumbnails = &mut self.thumbnails;
+        let objmap = &mut self.widgets.objmap;
+        let voxmap = &self.widgets.previewer.voxmap;
+
+        for (index, stamp, output) in self.evaluator.outputs() {
+            let widget = match objmap
+                .objs
+                .data_get_mut(&index)
+                .and_then(|x| x.as_widget_plugable_mut())
             {
-                for adj in self.widgets.objmap.get_next_widgets(elt).unwrap() {
-                    let b = mark.get(&adj);
-                    if b.is_none() || !*b.unwrap() {
-                        stack.push(adj);
-                    }
-                }
+                Some(widget) => widget,
+                None => continue,
+            };
 
-                let wigdet =
-                    self.widgets.objmap.objs.data_get_mut(&elt).unwrap();
-
-                match wigdet.get_type() {
-                    WidgetType::Perlin | WidgetType::Dsquare => {
-                        let widget = wigdet.as_widget_plugable_mut().unwrap();
-                        widget.apply(
-                            &mut self.widgets.previewer.voxmap,
-                            &mut self.globals,
-                        );
-                        mark.insert(elt, true);
-                    }
-                    WidgetType::Coloration => {
-                        if let Some(v) =
-                            self.widgets.objmap.get_prev_widgets(elt)
-                        {
-                            if v.len() == 1 {
-                                mark.insert(elt, true);
-
-                                let widget = self
-                                    .widgets
-                                    .objmap
-                                    .objs
-                                    .data_get_mut(&elt)
-                                    .unwrap()
-                                    .as_widget_plugable_mut()
-                                    .unwrap();
-
-                                widget.apply(
-                                    &mut self.widgets.previewer.voxmap,
-                                    &mut self.globals,
-                                );
+            if thumbnails.get(&index) == Some(&stamp) {
+                continue;
+            }
 
-                                break 'dfs;
-                            }
-                        }
-                    }
-                    WidgetType::Io => {
-                        if let Some(v) =
-                            self.widgets.objmap.get_prev_widgets(elt)
-                        {
-                            if !v.is_empty() {
-                                mark.insert(elt, true);
-                                break 'dfs;
-                            }
-                        }
-                    }
-                    WidgetType::None => {}
-                }
+            // the io blocks don't show any
+            thumbnails.insert(index, stamp);
+            if widget.get_type() == WidgetType::Io {
+                continue;
             }
-        }
 
-        if mark.len() < order || !mark.values().all(|x| *x) {
-            return Some(String::from("Some plugs aren't connected"));
+            let img = output.thumbnail(voxmap);
+            if let Ok(tex) = handle.load_texture_from_image(rl_thread, &img) {
+                widget.set_thumbnail(tex);
+            }
         }
-
-        None
     }
 
     pub fn run_generation(&mut self) {
-        let blocks = self.widgets.objmap.objs.data();
-
-        let start: Vec<Index> = blocks
-            .filter(|(_, widget)| widget.as_widget_plugable().is_some())
-            .filter(|(_, widget)| {
-                widget.get_type() == WidgetType::Io
-                    && widget.as_widget_plugable().unwrap().outputs().count()
-                        != 0
-                    && widget.as_widget_plugable().unwrap().inputs().count()
-                        == 0
-            })
-            .map(|(x, _)| x)
-            .copied()
-            .collect();
-
-        if start.is_empty() {
-            eprintln!("Couldn't find an entry point");
-            return;
-        } else if start.len() != 1 {
-            eprintln!("Cannot handle multiple entry point");
+        if let Err(msg) = evaluation_order(&self.widgets.objmap) {
+            eprintln!("ERROR: {}", msg);
             return;
         }
 
         eprint!("Generating... ");
 
-        if let Some(msg) = self.dfs_traversal(*start.first().unwrap()) {
-            eprintln!("FAILED");
-            eprintln!("ERROR: {}", msg);
-            return;
-        }
-
-        eprintln!("DONE");
+        self.evaluator.invalidate();
+        self.evaluator.request();
     }
 }
 
+fn selection_rect(rect: Rectangle) -> Rectangle {
+    Rectangle::new(
+        rect.x - SELECTION_OFFSET,
+        rect.y - SELECTION_OFFSET,
+        rect.width + SELECTION_OFFSET * 2.0,
+        rect.height + SELECTION_OFFSET * 2.0,
+    )
+}
+
 pub fn main() {
     // core::logging::set_trace_log(ffi::TraceLogLevel::LOG_WARNING);
     set_trace_log(ffi::TraceLogLevel::LOG_WARNING);
@@ -813,6 +1285,7 @@ pub fn main() {
     rl.set_exit_key(None);
 
     let mut ui = UI::default();
+    ui.widgets.chooser.set_library(load_library());
 
     // let mut globals.selection = Rectangle::EMPTY;
 
@@ -828,8 +1301,40 @@ pub fn main() {
     // let mut scroll = Vector2::zero();
 
     while !rl.window_should_close() && !ui.globals.quit {
+        if ui.evaluator.update(
+            &mut ui.widgets.objmap,
+            &mut ui.widgets.previewer.voxmap,
+            &mut ui.globals,
+        ) {
+            ui.widgets.previewer.shading_changed = true;
+        }
+        ui.update_thumbnails(&mut rl, &rl_thread);
+
+        if ui.widgets.previewer.shading_changed {
+            ui.widgets.previewer.update_shading(&ui.globals.altmap);
+            ui.widgets.viewer.changed = true;
+        }
+
         ui.widgets.previewer.render_to_texture(&mut rl, &rl_thread);
 
+        if ui.widgets.viewer.visible {
+            if ui.widgets.viewer.changed {
+                let previewer = &ui.widgets.previewer;
+                let colors =
+                    previewer.voxmap.render_to_img(previewer.light.as_ref());
+                let heights = previewer.heights(&ui.globals.altmap);
+                ui.widgets.viewer.rebuild(
+                    &mut rl,
+                    &rl_thread,
+                    &heights,
+                    &colors,
+                    ui.globals.biome_opts.deep_water,
+                );
+            }
+
+            ui.widgets.viewer.update_camera(&mut rl);
+        }
+
         let mut dhandle = rl.begin_drawing(&rl_thread);
 
         if dhandle.is_window_resized() {
@@ -842,6 +1347,13 @@ pub fn main() {
             ui.widgets.chooser.rect.height = ui.globals.dimensions.y;
         }
 
+        // the editor doesn't get the keys while hidden by the viewer
+        if ui.widgets.viewer.visible {
+            ui.viewer_keycalls(&mut dhandle);
+            ui.widgets.viewer.draw(&mut dhandle);
+            continue;
+        }
+
         ui.keycalls(&mut dhandle);
 
         let ns_mouse = dhandle.get_mouse_position();
@@ -956,6 +1468,8 @@ pub fn main() {
 
         if let Some(pos) = ui.globals.configuring {
             ui.configure_target(pos, &mut s_dhandle);
+        } else {
+            ui.globals.text_edit = None;
         }
 
         drop(s_dhandle);
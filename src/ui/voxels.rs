@@ -1,3 +1,5 @@
+use crate::display::build_2D::biome_color;
+use crate::display::hillshade::shade_channel;
 use raylib::prelude::*;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -22,6 +24,8 @@ pub struct VoxelMap {
     pub voxels: Vec<Voxel>,
     pub dims: Vector3,
     pub res: Vector3,
+    // values hold biome codes instead of altitudes
+    pub biomes: bool,
 }
 
 impl VoxelMap {
@@ -34,6 +38,7 @@ impl VoxelMap {
             voxels: vec![Voxel::default(); (elx * ely * elz) as usize],
             dims,
             res,
+            biomes: false,
         };
 
         for k in 0..(elz as usize) {
@@ -48,7 +53,19 @@ impl VoxelMap {
         res
     }
 
-    pub fn render_to_img(&self) -> Image {
+    pub fn heights(&self) -> Vec<Vec<f64>> {
+        let elx = (self.dims.x / self.res.x) as usize;
+        let ely = (self.dims.y / self.res.y) as usize;
+        let mut res = vec![vec![0.0; ely]; elx];
+
+        self.voxels.iter().for_each(|x| {
+            res[x.coords.x as usize][x.coords.y as usize] = x.value;
+        });
+
+        res
+    }
+
+    pub fn render_to_img(&self, light: Option<&Vec<Vec<f64>>>) -> Image {
         let mut img = Image::gen_image_color(
             self.dims.x as i32,
             self.dims.y as i32,
@@ -62,14 +79,28 @@ impl VoxelMap {
         for j in 0..(ely as usize) {
             for i in 0..(elx as usize) {
                 let vox = &self.voxels[j * (elx as usize) + i];
-                let col = (vox.value * 255.0) as u8;
+                let mut color = if self.biomes {
+                    biome_color(vox.value)
+                } else {
+                    let col = (vox.value * 255.0) as u8;
+                    rcolor(col, col, col, 255)
+                };
+
+                if let Some(l) = light
+                    .and_then(|x| x.get(vox.coords.x as usize))
+                    .and_then(|x| x.get(vox.coords.y as usize))
+                {
+                    color.r = shade_channel(color.r, *l);
+                    color.g = shade_channel(color.g, *l);
+                    color.b = shade_channel(color.b, *l);
+                }
 
                 img.draw_rectangle(
                     (vox.coords.x * self.res.x) as i32,
                     (vox.coords.y * self.res.y) as i32,
                     self.res.x as i32,
                     self.res.y as i32,
-                    rcolor(col, col, col, 255),
+                    color,
                 );
             }
         }
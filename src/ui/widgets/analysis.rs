@@ -0,0 +1,412 @@
+use crate::terrain_analysis::terrain_analysis::{
+    compute_layer, layer_to_unit, Layer,
+};
+use crate::ui::ui::WINDOW_BOX_TITLE_SIZE;
+use crate::widgets::*;
+
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+
+const ANALYSIS_BLOCK_COLOR: Color = Color::GREEN;
+const ANALYSIS_BLOCK_BORDER_COLOR: Color = Color::DARKGREEN;
+const ANALYSIS_BLOCK_SIZE: f32 = 65.0;
+const ANALYSIS_LAYERS: &str = "Slope;Aspect;Curvature;Flow";
+const I_PLUG_OFFSET: Vector2 = Vector2 {
+    x: 0.0,
+    y: ANALYSIS_BLOCK_SIZE / 2.0,
+};
+const O_PLUG_OFFSET: Vector2 = Vector2 {
+    x: ANALYSIS_BLOCK_SIZE,
+    y: ANALYSIS_BLOCK_SIZE / 2.0,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisOpts {
+    pub layer: Layer,
+    pub z_scale: f64,
+    // replaces the heightmap with the layer so that the previewer shows it
+    pub preview: bool,
+}
+
+impl Default for AnalysisOpts {
+    fn default() -> Self {
+        Self {
+            layer: Layer::Slope,
+            z_scale: 256.0,
+            preview: true,
+        }
+    }
+}
+
+pub struct Analysis {
+    pub opts: AnalysisOpts,
+    // last computed layer, rescaled into [0, 1]
+    pub grid: Vec<Vec<f64>>,
+
+    pub i_plug: PlugInterface,
+    pub thumbnail: Option<Texture2D>,
+
+    pub rect: Rectangle,
+    pub id: String,
+    pub ready: bool,
+    pub visible: bool,
+}
+
+impl Analysis {
+    pub fn create(objmap: &ObjMap, globals: &mut Globals) -> Self {
+        let mut res = Analysis::default();
+        let mouse = globals.r_mouse;
+
+        res.rect = Rectangle::new(
+            mouse.x,
+            mouse.y,
+            ANALYSIS_BLOCK_SIZE,
+            ANALYSIS_BLOCK_SIZE,
+        );
+        res.id = format!("{}_{}", res.id, objmap.objs.unique_keys_count());
+
+        let input = mouse + I_PLUG_OFFSET;
+        let output = mouse + O_PLUG_OFFSET;
+
+        res.i_plug = PlugInterface::new(mouse);
+
+        res.i_plug.inputs.insert(input.into(), Vec::new());
+        res.i_plug.outputs.insert(output.into(), Vec::new());
+
+        res
+    }
+
+    pub fn export_image(&self) {
+        if self.grid.is_empty() {
+            eprintln!("Nothing to export, run the generation first");
+            return;
+        }
+
+        let mut image = Image::gen_image_color(
+            self.grid.len() as i32,
+            self.grid[0].len() as i32,
+            Color::WHITE,
+        );
+
+        self.grid.iter().enumerate().for_each(|(i, row)| {
+            row.iter().enumerate().for_each(|(j, x)| {
+                let col = (x * 255.0) as u8;
+                image.draw_pixel(
+                    i as i32,
+                    j as i32,
+                    rcolor(col, col, col, 255),
+                );
+            })
+        });
+
+        let path = format!("analysis_{:?}.png", self.opts.layer).to_lowercase();
+        image.export_image(&path);
+        println!("Exporting {}... DONE", path);
+    }
+}
+
+impl Default for Analysis {
+    fn default() -> Self {
+        Self {
+            opts: AnalysisOpts::default(),
+            grid: Vec::new(),
+            i_plug: PlugInterface::default(),
+            thumbnail: None,
+
+            rect: Rectangle::EMPTY,
+            id: String::from("Analysis"),
+            ready: false,
+            visible: true,
+        }
+    }
+}
+
+impl WidgetRectangle for Analysis {
+    fn get_rect(&self) -> Rectangle {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rectangle) {
+        self.rect = rect;
+    }
+}
+
+impl WidgetCollidable for Analysis {}
+
+impl WidgetPlugable for Analysis {
+    fn get_iplug(&self) -> &PlugInterface {
+        &self.i_plug
+    }
+
+    fn get_iplug_mut(&mut self) -> &mut PlugInterface {
+        &mut self.i_plug
+    }
+
+    fn get_thumbnail(&self) -> Option<&Texture2D> {
+        self.thumbnail.as_ref()
+    }
+
+    fn set_thumbnail(&mut self, thumbnail: Texture2D) {
+        self.thumbnail = Some(thumbnail);
+    }
+
+    fn replace_plug_pos(
+        &mut self,
+        pos: Vector2,
+        new: Vector2,
+    ) -> Option<Vec<Vector2>> {
+        if let Some(i) = self.i_plug.inputs.remove(&pos.into()) {
+            if let Some(c) = self.i_plug.inputs.insert(new.into(), i) {
+                return Some(c);
+            }
+
+            return None;
+        }
+
+        if let Some(i) = self.i_plug.outputs.remove(&pos.into()) {
+            if let Some(c) = self.i_plug.outputs.insert(new.into(), i) {
+                return Some(c);
+            }
+
+            return None;
+        }
+
+        None
+    }
+
+    fn translate_wplugs(&mut self, offset: Vector2) {
+        let old_pos = self.get_pos();
+        self.translate(offset);
+        let new_pos = self.get_pos();
+        self.replace_plug_pos(old_pos + I_PLUG_OFFSET, new_pos + I_PLUG_OFFSET);
+        self.replace_plug_pos(old_pos + O_PLUG_OFFSET, new_pos + O_PLUG_OFFSET);
+    }
+
+    fn apply(&mut self, voxmap: &mut VoxelMap, _: &mut Globals) {
+        let mut base =
+            vec![vec![0.0; voxmap.dims.y as usize]; voxmap.dims.x as usize];
+
+        voxmap.voxels.iter().for_each(|x| {
+            base[x.coords.x as usize][x.coords.y as usize] = x.value;
+        });
+
+        let layer = compute_layer(&base, self.opts.layer, self.opts.z_scale);
+        self.grid = layer_to_unit(&layer, self.opts.layer);
+
+        if !self.opts.preview {
+            return;
+        }
+
+        voxmap.voxels.iter_mut().for_each(|x| {
+            let cx = x.coords.x as usize;
+            let cy = x.coords.y as usize;
+            x.value = self.grid[cx][cy];
+        });
+    }
+
+    fn take_grid(&mut self) -> Vec<Vec<f64>> {
+        std::mem::take(&mut self.grid)
+    }
+
+    fn set_grid(&mut self, grid: Vec<Vec<f64>>) {
+        self.grid = grid;
+    }
+}
+
+impl WidgetConfigurable for Analysis {
+    fn configure(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        let panel_rect =
+            Rectangle::new(0.0, 0.0, 150.0, globals.dimensions.y + 1.0);
+
+        globals.workspace_locked = panel_rect
+            .check_collision_circle_rec(globals.s_mouse, SELECTION_OFFSET);
+
+        handle.gui_window_box(
+            panel_rect,
+            Some(&CString::new(&*self.id).expect("CString::new failed")),
+        );
+
+        let layer = handle.gui_combo_box(
+            Rectangle::new(5.0, WINDOW_BOX_TITLE_SIZE + 5.0, 140.0, 30.0),
+            Some(&CString::new(ANALYSIS_LAYERS).expect("CString::new failed")),
+            self.opts.layer as i32,
+        );
+
+        self.opts.layer = match layer {
+            1 => Layer::Aspect,
+            2 => Layer::Curvature,
+            3 => Layer::FlowAccumulation,
+            _ => Layer::Slope,
+        };
+
+        handle.gui_group_box(
+            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 50.0, 120.0, 20.0),
+            Some(
+                &CString::new(format!("Z scale: {}", self.opts.z_scale))
+                    .expect("CString::new failed"),
+            ),
+        );
+
+        self.opts.z_scale = handle
+            .gui_slider_bar(
+                Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 57.0, 120.0, 20.0),
+                Some(&CString::new("1").expect("CString::new failed")),
+                Some(&CString::new("512").expect("CString::new failed")),
+                self.opts.z_scale as f32,
+                1.0,
+                512.0,
+            )
+            .round() as f64;
+
+        self.opts.preview = handle.gui_toggle(
+            Rectangle::new(5.0, WINDOW_BOX_TITLE_SIZE + 87.0, 140.0, 30.0),
+            Some(&CString::new("preview layer").expect("CString::new failed")),
+            self.opts.preview,
+        );
+
+        if handle.gui_button(
+            Rectangle::new(5.0, WINDOW_BOX_TITLE_SIZE + 122.0, 140.0, 30.0),
+            Some(&CString::new("Export PNG").expect("CString::new failed")),
+        ) {
+            self.export_image();
+        }
+
+        if handle.gui_button(
+            Rectangle::new(5.0, globals.dimensions.y - 35.0, 140.0, 30.0),
+            Some(&CString::new("Reset").expect("CString::new failed")),
+        ) {
+            self.opts = AnalysisOpts::default();
+        }
+
+        WidgetSignal::None
+    }
+
+    fn get_opts(&self) -> WidgetOpts {
+        WidgetOpts::Analysis(self.opts.clone())
+    }
+
+    fn set_opts(&mut self, opts: WidgetOpts) -> WidgetSignal {
+        if let WidgetOpts::Analysis(opts) = opts {
+            self.opts = opts;
+        }
+
+        WidgetSignal::None
+    }
+}
+
+impl Widget for Analysis {
+    fn render(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        self.draw_plug_links(handle);
+
+        handle.draw_rectangle_rec(self.rect, ANALYSIS_BLOCK_COLOR);
+        handle.draw_rectangle_lines_ex(
+            self.rect,
+            2,
+            ANALYSIS_BLOCK_BORDER_COLOR,
+        );
+
+        handle.draw_text(
+            &self.id,
+            self.rect.x as i32 + 2,
+            self.rect.y as i32 + 5,
+            15,
+            Color::WHITE,
+        );
+
+        self.draw_thumbnail(handle, THUMBNAIL_TOP);
+        self.draw_plugs(handle, globals);
+
+        WidgetSignal::None
+    }
+
+    fn call(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        self.render(handle, globals)
+    }
+
+    fn ready(&mut self) {
+        self.ready = true
+    }
+    fn unready(&mut self) {
+        self.ready = false
+    }
+    fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    fn get_type(&self) -> WidgetType {
+        WidgetType::Analysis
+    }
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+    fn set_id(&mut self, id: String) {
+        self.id = id
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+    fn set_visible(&mut self, state: bool) {
+        self.visible = state
+    }
+    fn show(&mut self) {
+        self.visible = true
+    }
+    fn hide(&mut self) {
+        self.visible = false
+    }
+    fn toggle_visible(&mut self) {
+        self.visible ^= true
+    }
+
+    fn as_widget_rectangle(&self) -> Option<&dyn WidgetRectangle> {
+        Some(self as _)
+    }
+
+    fn as_widget_rectangle_mut(&mut self) -> Option<&mut dyn WidgetRectangle> {
+        Some(self as _)
+    }
+
+    fn as_widget_collidable(&self) -> Option<&dyn WidgetCollidable> {
+        Some(self as _)
+    }
+
+    fn as_widget_collidable_mut(
+        &mut self,
+    ) -> Option<&mut dyn WidgetCollidable> {
+        Some(self as _)
+    }
+
+    fn as_widget_plugable(&self) -> Option<&dyn WidgetPlugable> {
+        Some(self as _)
+    }
+
+    fn as_widget_plugable_mut(&mut self) -> Option<&mut dyn WidgetPlugable> {
+        Some(self as _)
+    }
+
+    fn as_widget_configurable(&self) -> Option<&dyn WidgetConfigurable> {
+        Some(self as _)
+    }
+
+    fn as_widget_configurable_mut(
+        &mut self,
+    ) -> Option<&mut dyn WidgetConfigurable> {
+        Some(self as _)
+    }
+}
@@ -1,26 +1,57 @@
 // use crate::diamond_square::diamond_square::{self, *};
 use crate::display::build_2D::{
-    apply_biomes, diamond_square, generate_perlin_noise_matrix, normalize,
+    apply_biomes_with, generate_seeded_perlin_noise_matrix, normalize,
+    BiomeOpts,
 };
+use crate::display::climate::{humidity_map, temperature_map, ClimateOpts};
 use crate::ui::ui::WINDOW_BOX_TITLE_SIZE;
+use crate::widgets::number_box::*;
 use crate::widgets::*;
 
-use noise::{NoiseFn, Perlin};
 use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::ffi::CString;
 
 const COLORATION_BLOCK_COLOR1: Color = Color::PINK;
 const COLORATION_BLOCK_COLOR2: Color = Color::PURPLE;
 const COLORATION_BLOCK_SIZE: f32 = 100.0;
 
-const I_PLUG_OFFSET: Vector2 = Vector2 {
-    x: 0.0,
+// altitude, temperature and humidity, from top to bottom
+const I_PLUG_OFFSETS: [Vector2; 3] = [
+    Vector2 {
+        x: 0.0,
+        y: COLORATION_BLOCK_SIZE / 4.0,
+    },
+    Vector2 {
+        x: 0.0,
+        y: COLORATION_BLOCK_SIZE / 2.0,
+    },
+    Vector2 {
+        x: 0.0,
+        y: COLORATION_BLOCK_SIZE * 3.0 / 4.0,
+    },
+];
+const I_PLUG_LABELS: [&str; 3] = ["A", "T", "H"];
+const O_PLUG_OFFSET: Vector2 = Vector2 {
+    x: COLORATION_BLOCK_SIZE,
     y: COLORATION_BLOCK_SIZE / 2.0,
 };
 
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorationOpts {
+    // used for the temperature and the humidity left unplugged
+    pub climate: ClimateOpts,
+    pub biomes: BiomeOpts,
+    // of the noise of the temperature left unplugged, the map only changes
+    // with the options
+    pub seed: u32,
+}
+
 pub struct Coloration {
-    // pub opts: ColorationOpts,
+    pub opts: ColorationOpts,
     pub i_plug: PlugInterface,
+    pub thumbnail: Option<Texture2D>,
 
     pub rect: Rectangle,
     pub id: String,
@@ -41,21 +72,46 @@ impl Coloration {
         );
         res.id = format!("{}_{}", res.id, objmap.objs.unique_keys_count());
 
-        let i = mouse + I_PLUG_OFFSET;
-
         res.i_plug = PlugInterface::new(mouse);
 
-        res.i_plug.inputs.insert(i.into(), Vec::new());
+        I_PLUG_OFFSETS.iter().for_each(|x| {
+            res.i_plug.inputs.insert((mouse + *x).into(), Vec::new());
+        });
+        res.i_plug
+            .outputs
+            .insert((mouse + O_PLUG_OFFSET).into(), Vec::new());
 
         res
     }
 }
 
+// Secondary input of `apply` as a grid. Its values are kept as they are,
+// only clamped into [0, 1], so that the thresholds stay absolute.
+fn input_grid(
+    voxmap: &VoxelMap,
+    globals: &Globals,
+    input: usize,
+) -> Option<Vec<Vec<f64>>> {
+    let values = globals.inputs.get(input)?.as_ref()?;
+    let mut res =
+        vec![vec![0.0; voxmap.dims.y as usize]; voxmap.dims.x as usize];
+
+    voxmap.voxels.iter().zip(values.iter()).for_each(|(x, y)| {
+        res[x.coords.x as usize][x.coords.y as usize] = y.clamp(0.0, 1.0);
+    });
+
+    Some(res)
+}
+
 impl Default for Coloration {
     fn default() -> Self {
         Self {
-            // opts: ColorationOpts::default(),
+            opts: ColorationOpts {
+                seed: rand::random(),
+                ..ColorationOpts::default()
+            },
             i_plug: PlugInterface::default(),
+            thumbnail: None,
 
             rect: Rectangle::EMPTY,
             id: String::from("Coloration"),
@@ -86,6 +142,14 @@ impl WidgetPlugable for Coloration {
         &mut self.i_plug
     }
 
+    fn get_thumbnail(&self) -> Option<&Texture2D> {
+        self.thumbnail.as_ref()
+    }
+
+    fn set_thumbnail(&mut self, thumbnail: Texture2D) {
+        self.thumbnail = Some(thumbnail);
+    }
+
     fn replace_plug_pos(
         &mut self,
         pos: Vector2,
@@ -114,49 +178,51 @@ impl WidgetPlugable for Coloration {
         let old_pos = self.get_pos();
         self.translate(offset);
         let new_pos = self.get_pos();
-        self.replace_plug_pos(old_pos + I_PLUG_OFFSET, new_pos + I_PLUG_OFFSET);
+        I_PLUG_OFFSETS.iter().for_each(|x| {
+            self.replace_plug_pos(old_pos + *x, new_pos + *x);
+        });
+        self.replace_plug_pos(old_pos + O_PLUG_OFFSET, new_pos + O_PLUG_OFFSET);
     }
 
+    // the altitude comes from the voxel map, the temperature and the humidity
+    // from the other plugs or from the climate model when they're unplugged
     fn apply(&mut self, voxmap: &mut VoxelMap, globals: &mut Globals) {
-        let mut base =
-            vec![vec![0.0; voxmap.dims.x as usize]; voxmap.dims.y as usize];
-
-        voxmap.voxels.iter().for_each(|x| {
-            base[x.coords.x as usize][x.coords.y as usize] = x.value;
-        });
-
-        globals.altmap = generate_perlin_noise_matrix(
-            voxmap.dims.x as usize,
-            voxmap.dims.y as usize,
-            90.0,
-        );
-
-        globals.altmap = diamond_square(9);
-        normalize(&mut globals.altmap);
-        globals.altmap.pop();
-        globals.altmap.iter_mut().for_each(|x| {
-            x.pop();
+        let mut altitude = voxmap.heights();
+        normalize(&mut altitude);
+
+        let temperature = input_grid(voxmap, globals, 0).unwrap_or_else(|| {
+            let noise = generate_seeded_perlin_noise_matrix(
+                voxmap.dims.x as usize,
+                voxmap.dims.y as usize,
+                90.0,
+                self.opts.seed,
+            );
+
+            temperature_map(&altitude, &noise, &self.opts.climate)
         });
-
-        let tmp_map = generate_perlin_noise_matrix(
-            voxmap.dims.x as usize,
-            voxmap.dims.y as usize,
-            90.0,
-        );
-
-        let hum_map = generate_perlin_noise_matrix(
-            voxmap.dims.x as usize,
-            voxmap.dims.y as usize,
-            90.0,
+        let humidity = input_grid(voxmap, globals, 1)
+            .unwrap_or_else(|| humidity_map(&altitude, &self.opts.climate));
+
+        let mut base = altitude.clone();
+        let (settlements, roads) = apply_biomes_with(
+            &mut base,
+            &altitude,
+            &temperature,
+            &humidity,
+            &self.opts.biomes,
         );
 
-        apply_biomes(&mut base, &globals.altmap, &tmp_map, &hum_map);
-
         voxmap.voxels.iter_mut().for_each(|x| {
             let cx = x.coords.x as usize;
             let cy = x.coords.y as usize;
             x.value = base[cx][cy];
         });
+
+        globals.altmap = altitude;
+        globals.biome_opts = self.opts.biomes.clone();
+        globals.settlements = settlements;
+        globals.roads = roads;
+        voxmap.biomes = true;
     }
 }
 
@@ -177,6 +243,125 @@ impl WidgetConfigurable for Coloration {
             Some(&CString::new(&*self.id).expect("CString::new failed")),
         );
 
+        let id = self.id.clone();
+        let row = |i: usize| {
+            Vector2::new(15.0, WINDOW_BOX_TITLE_SIZE + 10.0 + 37.0 * i as f32)
+        };
+        let climate = &mut self.opts.climate;
+        let biomes = &mut self.opts.biomes;
+
+        climate.wind_direction = number_slider(
+            handle,
+            globals,
+            (&id, 0),
+            row(0),
+            "Wind (°)",
+            climate.wind_direction as f32,
+            (0.0, 360.0),
+        )
+        .round() as f64;
+
+        climate.orographic_lift = ((number_slider(
+            handle,
+            globals,
+            (&id, 1),
+            row(1),
+            "Orographic",
+            climate.orographic_lift as f32,
+            (0.0, 10.0),
+        ) * 10.0)
+            .round()
+            / 10.0) as f64;
+
+        let thresholds = [
+            ("Deep water", &mut biomes.deep_water),
+            ("Shallow water", &mut biomes.deep),
+            ("Montane", &mut biomes.montane),
+            ("Mountain", &mut biomes.mountain),
+            ("Snow", &mut biomes.snow),
+            ("Temperate", &mut biomes.temperate),
+            ("Wet", &mut biomes.wet),
+            ("Marsh humidity", &mut biomes.marsh_humidity),
+        ];
+
+        for (i, (title, value)) in thresholds.into_iter().enumerate() {
+            *value = ((number_slider(
+                handle,
+                globals,
+                (&id, i + 2),
+                row(i + 2),
+                title,
+                *value as f32,
+                (0.0, 1.0),
+            ) * 100.0)
+                .round()
+                / 100.0) as f64;
+        }
+
+        let passes = [
+            ("marsh", &mut biomes.marsh),
+            ("desert cleanup", &mut biomes.desert_cleanup),
+            ("settlements", &mut biomes.settlements),
+            ("roads", &mut biomes.roads),
+        ];
+        let top = row(10).y;
+        let passes_count = passes.len();
+
+        for (i, (title, value)) in passes.into_iter().enumerate() {
+            *value = handle.gui_toggle(
+                Rectangle::new(5.0, top + 25.0 * i as f32, 140.0, 20.0),
+                Some(&CString::new(title).expect("CString::new failed")),
+                *value,
+            );
+        }
+
+        let seed = Vector2::new(15.0, top + 25.0 * passes_count as f32 + 10.0);
+
+        handle.gui_group_box(
+            Rectangle::new(seed.x, seed.y, 120.0, 20.0),
+            Some(&CString::new("Seed").expect("CString::new failed")),
+        );
+
+        if let Some(typed) = number_box(
+            handle,
+            globals,
+            (&id, 10),
+            Rectangle::new(seed.x, seed.y + 7.0, 120.0, 20.0),
+            self.opts.seed,
+        ) {
+            self.opts.seed = typed;
+        }
+
+        if handle.gui_button(
+            Rectangle::new(seed.x, seed.y + 32.0, 120.0, 20.0),
+            Some(&CString::new("Randomize").expect("CString::new failed")),
+        ) {
+            self.opts.seed = rand::random();
+        }
+
+        if handle.gui_button(
+            Rectangle::new(5.0, globals.dimensions.y - 35.0, 140.0, 30.0),
+            Some(&CString::new("Reset").expect("CString::new failed")),
+        ) {
+            // the seed has its own button
+            self.opts = ColorationOpts {
+                seed: self.opts.seed,
+                ..ColorationOpts::default()
+            };
+        }
+
+        WidgetSignal::None
+    }
+
+    fn get_opts(&self) -> WidgetOpts {
+        WidgetOpts::Coloration(self.opts.clone())
+    }
+
+    fn set_opts(&mut self, opts: WidgetOpts) -> WidgetSignal {
+        if let WidgetOpts::Coloration(opts) = opts {
+            self.opts = opts;
+        }
+
         WidgetSignal::None
     }
 }
@@ -200,6 +385,20 @@ impl Widget for Coloration {
             Color::WHITE,
         );
 
+        I_PLUG_LABELS
+            .iter()
+            .zip(I_PLUG_OFFSETS.iter())
+            .for_each(|(x, y)| {
+                handle.draw_text(
+                    x,
+                    (self.rect.x + y.x) as i32 + 6,
+                    (self.rect.y + y.y) as i32 - 5,
+                    10,
+                    Color::WHITE,
+                );
+            });
+
+        self.draw_thumbnail(handle, THUMBNAIL_TOP);
         self.draw_plugs(handle, globals);
 
         WidgetSignal::None
@@ -22,6 +22,7 @@ pub struct Dsquare {
     pub opts: DsquareOpts,
 
     pub i_plug: PlugInterface,
+    pub thumbnail: Option<Texture2D>,
 
     pub rect: Rectangle,
     pub id: String,
@@ -59,6 +60,7 @@ impl Default for Dsquare {
         Self {
             opts: DsquareOpts::default(),
             i_plug: PlugInterface::default(),
+            thumbnail: None,
 
             rect: Rectangle::EMPTY,
             id: String::from("Dsquare"),
@@ -89,6 +91,14 @@ impl WidgetPlugable for Dsquare {
         &mut self.i_plug
     }
 
+    fn get_thumbnail(&self) -> Option<&Texture2D> {
+        self.thumbnail.as_ref()
+    }
+
+    fn set_thumbnail(&mut self, thumbnail: Texture2D) {
+        self.thumbnail = Some(thumbnail);
+    }
+
     fn replace_plug_pos(
         &mut self,
         pos: Vector2,
@@ -184,6 +194,18 @@ impl WidgetConfigurable for Dsquare {
 
         WidgetSignal::None
     }
+
+    fn get_opts(&self) -> WidgetOpts {
+        WidgetOpts::Dsquare(self.opts.clone())
+    }
+
+    fn set_opts(&mut self, opts: WidgetOpts) -> WidgetSignal {
+        if let WidgetOpts::Dsquare(opts) = opts {
+            self.opts = opts;
+        }
+
+        WidgetSignal::None
+    }
 }
 
 impl Widget for Dsquare {
@@ -209,6 +231,7 @@ impl Widget for Dsquare {
             Color::WHITE,
         );
 
+        self.draw_thumbnail(handle, THUMBNAIL_TOP);
         self.draw_plugs(handle, globals);
 
         WidgetSignal::None
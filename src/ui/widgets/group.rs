@@ -0,0 +1,672 @@
+// A group is a sub-graph collapsed into a single block. The inputs and
+// outputs of the block are the plugs the sub-graph leaves dangling, the
+// sub-graph itself is kept in coordinates relative to its top left corner so
+// that it can be saved to the library and expanded anywhere.
+
+use crate::evaluator::{evaluate, NodeOutput};
+use crate::history::*;
+use crate::maths::Vec2u;
+use crate::ui::ui::WINDOW_BOX_TITLE_SIZE;
+use crate::widgets::*;
+
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const GROUP_BLOCK_COLOR: Color = Color::PURPLE;
+const GROUP_BLOCK_BORDER_COLOR: Color = Color::DARKPURPLE;
+const GROUP_BLOCK_WIDTH: f32 = 80.0;
+// below the name of the group
+const GROUP_THUMBNAIL_TOP: f32 = 36.0;
+const GROUP_PLUG_SPACING: f32 = 30.0;
+pub const LIBRARY_DIR: &str = "./library";
+
+// a position in the coordinates of the sub-graph
+pub type Point = (f32, f32);
+
+fn to_point(v: Vector2) -> Point {
+    (v.x, v.y)
+}
+
+fn to_vector(p: Point) -> Vector2 {
+    Vector2::new(p.0, p.1)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GroupNode {
+    pub kind: WidgetType,
+    pub id: String,
+    // x, y, width, height
+    pub rect: (f32, f32, f32, f32),
+    pub inputs: Vec<Point>,
+    pub outputs: Vec<Point>,
+    pub opts: WidgetOpts,
+}
+
+impl GroupNode {
+    fn from_snapshot(snapshot: &WidgetSnapshot) -> Self {
+        let rect = snapshot.rect;
+        let points = |plugs: &HashMap<Vec2u, Vec<Vector2>>| {
+            let mut res: Vec<Point> =
+                plugs.keys().map(|x| to_point((*x).into())).collect();
+            res.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            res
+        };
+
+        Self {
+            kind: snapshot.kind,
+            id: snapshot.id.clone(),
+            rect: (rect.x, rect.y, rect.width, rect.height),
+            inputs: points(&snapshot.plugs.inputs),
+            outputs: points(&snapshot.plugs.outputs),
+            opts: snapshot.opts.clone(),
+        }
+    }
+
+    fn snapshot(&self) -> WidgetSnapshot {
+        let (x, y, width, height) = self.rect;
+        let mut plugs = PlugInterface::new(Vector2::new(x, y));
+
+        self.inputs.iter().for_each(|p| {
+            plugs.inputs.insert(to_vector(*p).into(), Vec::new());
+        });
+        self.outputs.iter().for_each(|p| {
+            plugs.outputs.insert(to_vector(*p).into(), Vec::new());
+        });
+
+        WidgetSnapshot {
+            kind: self.kind,
+            id: self.id.clone(),
+            rect: Rectangle::new(x, y, width, height),
+            plugs,
+            opts: self.opts.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GroupOpts {
+    pub name: String,
+    pub nodes: Vec<GroupNode>,
+    // (output plug, input plug) between the nodes
+    pub links: Vec<(Point, Point)>,
+    // plugs of the nodes exposed by the group, in the order of its own plugs
+    pub inputs: Vec<Point>,
+    pub outputs: Vec<Point>,
+}
+
+impl GroupOpts {
+    // The widgets as a sub-graph, with `external` the links going out of
+    // it. Returns the top left corner of the widgets and where each of their
+    // plugs went in the sub-graph.
+    pub fn collapse(
+        snapshots: &[WidgetSnapshot],
+        links: &[Link],
+        external: &[Link],
+    ) -> Option<(Self, Vector2, HashMap<Vec2u, Vector2>)> {
+        let anchor = anchor(snapshots)?;
+        let (moved, plugs) = translate_snapshots(snapshots, -anchor)?;
+        let point = |x: &Vector2| plugs.get(&(*x).into()).map(|x| to_point(*x));
+
+        let mut nodes: Vec<GroupNode> =
+            moved.iter().map(GroupNode::from_snapshot).collect();
+        nodes.sort_by(|a, b| a.rect.partial_cmp(&b.rect).unwrap());
+
+        let links: Vec<(Point, Point)> = links
+            .iter()
+            .filter_map(|(x, y)| Some((point(x)?, point(y)?)))
+            .collect();
+        let linked_out =
+            |p: &Point| external.iter().any(|(x, _)| point(x) == Some(*p));
+
+        // an output can feed both the sub-graph and the rest of the graph
+        let inputs = nodes
+            .iter()
+            .flat_map(|x| x.inputs.iter())
+            .filter(|p| links.iter().all(|(_, y)| y != *p))
+            .copied()
+            .collect();
+        let outputs = nodes
+            .iter()
+            .flat_map(|x| x.outputs.iter())
+            .filter(|p| linked_out(p) || links.iter().all(|(x, _)| x != *p))
+            .copied()
+            .collect();
+
+        let mut res = Self {
+            name: String::new(),
+            nodes,
+            links,
+            inputs,
+            outputs,
+        };
+        res.name = res.default_name();
+
+        Some((res, anchor, plugs))
+    }
+
+    // the nodes moved to `pos` and linked together, along with where each
+    // point of the sub-graph went
+    pub fn expand(
+        &self,
+        pos: Vector2,
+    ) -> Option<(Vec<WidgetSnapshot>, Vec<Link>, HashMap<Vec2u, Vector2>)> {
+        let snapshots: Vec<WidgetSnapshot> =
+            self.nodes.iter().map(|x| x.snapshot()).collect();
+        let (moved, plugs) = translate_snapshots(&snapshots, pos)?;
+
+        let links = self
+            .links
+            .iter()
+            .filter_map(|(x, y)| {
+                Some((
+                    *plugs.get(&to_vector(*x).into())?,
+                    *plugs.get(&to_vector(*y).into())?,
+                ))
+            })
+            .collect();
+
+        Some((moved, links, plugs))
+    }
+
+    // nodes sorted so that each one comes after the nodes feeding it
+    pub fn order(&self) -> Vec<usize> {
+        let owner = |p: &Point| {
+            self.nodes
+                .iter()
+                .position(|x| x.inputs.contains(p) || x.outputs.contains(p))
+        };
+        let edges: Vec<(usize, usize)> = self
+            .links
+            .iter()
+            .filter_map(|(x, y)| Some((owner(x)?, owner(y)?)))
+            .collect();
+
+        let mut done = vec![false; self.nodes.len()];
+        let mut res = Vec::with_capacity(self.nodes.len());
+
+        while let Some(i) = (0..self.nodes.len()).find(|&i| {
+            !done[i] && edges.iter().all(|&(x, y)| y != i || done[x])
+        }) {
+            done[i] = true;
+            res.push(i);
+        }
+
+        res
+    }
+
+    fn default_name(&self) -> String {
+        self.order()
+            .into_iter()
+            .filter_map(|i| new_widget(self.nodes[i].kind))
+            .map(|x| x.get_id())
+            .collect::<Vec<String>>()
+            .join("-")
+    }
+
+    pub fn size(&self) -> Vector2 {
+        let plugs = self.inputs.len().max(self.outputs.len()).max(2);
+        Vector2::new(GROUP_BLOCK_WIDTH, GROUP_PLUG_SPACING * plugs as f32)
+    }
+
+    pub fn input_plug(&self, pos: Vector2, i: usize) -> Vector2 {
+        pos + Vector2::new(0.0, GROUP_PLUG_SPACING * (i as f32 + 0.5))
+    }
+
+    pub fn output_plug(&self, pos: Vector2, i: usize) -> Vector2 {
+        pos + Vector2::new(
+            GROUP_BLOCK_WIDTH,
+            GROUP_PLUG_SPACING * (i as f32 + 0.5),
+        )
+    }
+
+    // plug of the group at `pos` exposing `point` of the sub-graph
+    pub fn exposed_plug(
+        &self,
+        pos: Vector2,
+        point: Vector2,
+    ) -> Option<Vector2> {
+        let point = to_point(point);
+
+        if let Some(i) = self.inputs.iter().position(|x| *x == point) {
+            return Some(self.input_plug(pos, i));
+        }
+
+        let i = self.outputs.iter().position(|x| *x == point)?;
+        Some(self.output_plug(pos, i))
+    }
+
+    // point of the sub-graph exposed by the plug of the group at `pos`
+    pub fn inner_plug(&self, pos: Vector2, plug: Vector2) -> Option<Vector2> {
+        if let Some(i) =
+            (0..self.inputs.len()).find(|i| self.input_plug(pos, *i) == plug)
+        {
+            return Some(to_vector(self.inputs[i]));
+        }
+
+        let i = (0..self.outputs.len())
+            .find(|i| self.output_plug(pos, *i) == plug)?;
+        Some(to_vector(self.outputs[i]))
+    }
+}
+
+// A group of the library is never replaced, a number is added to the name
+// of the file when it's taken.
+pub fn save_group(opts: &GroupOpts) -> io::Result<PathBuf> {
+    fs::create_dir_all(LIBRARY_DIR)?;
+
+    let mut n = 1;
+    loop {
+        let name = match n {
+            1 => opts.name.clone(),
+            n => format!("{}_{n}", opts.name),
+        };
+        let path = Path::new(LIBRARY_DIR).join(format!("{name}.json"));
+
+        match File::options().write(true).create_new(true).open(&path) {
+            Ok(file) => {
+                serde_json::to_writer_pretty(BufWriter::new(file), opts)?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// groups of the library, named after their file
+pub fn load_library() -> Vec<GroupOpts> {
+    let entries = match fs::read_dir(LIBRARY_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut res: Vec<GroupOpts> = entries
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.extension().map_or(false, |ext| ext == "json"))
+        .filter_map(|path| {
+            let file = BufReader::new(File::open(&path).ok()?);
+
+            match serde_json::from_reader::<_, GroupOpts>(file) {
+                Ok(mut opts) => {
+                    opts.name = path.file_stem()?.to_string_lossy().into();
+                    Some(opts)
+                }
+                Err(e) => {
+                    eprintln!("Skipping {}: {e}", path.display());
+                    None
+                }
+            }
+        })
+        .collect();
+
+    res.sort_by(|a, b| a.name.cmp(&b.name));
+    res
+}
+
+pub struct Group {
+    pub opts: GroupOpts,
+
+    pub i_plug: PlugInterface,
+    pub thumbnail: Option<Texture2D>,
+
+    pub rect: Rectangle,
+    pub id: String,
+    pub ready: bool,
+    pub visible: bool,
+}
+
+impl Group {
+    pub fn new(objmap: &ObjMap, pos: Vector2, opts: GroupOpts) -> Self {
+        let mut res = Group::default();
+        let size = opts.size();
+
+        res.rect = Rectangle::new(pos.x, pos.y, size.x, size.y);
+        res.id = format!("{}_{}", res.id, objmap.objs.unique_keys_count());
+        res.i_plug = PlugInterface::new(pos);
+
+        (0..opts.inputs.len()).for_each(|i| {
+            let input = opts.input_plug(pos, i);
+            res.i_plug.inputs.insert(input.into(), Vec::new());
+        });
+        (0..opts.outputs.len()).for_each(|i| {
+            let output = opts.output_plug(pos, i);
+            res.i_plug.outputs.insert(output.into(), Vec::new());
+        });
+
+        res.opts = opts;
+        res
+    }
+
+    pub fn create(
+        objmap: &ObjMap,
+        globals: &mut Globals,
+        opts: GroupOpts,
+    ) -> Self {
+        Self::new(objmap, globals.r_mouse, opts)
+    }
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Self {
+            opts: GroupOpts::default(),
+            i_plug: PlugInterface::default(),
+            thumbnail: None,
+
+            rect: Rectangle::EMPTY,
+            id: String::from("Group"),
+            ready: false,
+            visible: true,
+        }
+    }
+}
+
+impl WidgetRectangle for Group {
+    fn get_rect(&self) -> Rectangle {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rectangle) {
+        self.rect = rect;
+    }
+}
+
+impl WidgetCollidable for Group {}
+
+impl WidgetPlugable for Group {
+    fn get_iplug(&self) -> &PlugInterface {
+        &self.i_plug
+    }
+
+    fn get_iplug_mut(&mut self) -> &mut PlugInterface {
+        &mut self.i_plug
+    }
+
+    fn get_thumbnail(&self) -> Option<&Texture2D> {
+        self.thumbnail.as_ref()
+    }
+
+    fn set_thumbnail(&mut self, thumbnail: Texture2D) {
+        self.thumbnail = Some(thumbnail);
+    }
+
+    fn replace_plug_pos(
+        &mut self,
+        pos: Vector2,
+        new: Vector2,
+    ) -> Option<Vec<Vector2>> {
+        if let Some(i) = self.i_plug.inputs.remove(&pos.into()) {
+            return self.i_plug.inputs.insert(new.into(), i);
+        }
+
+        if let Some(i) = self.i_plug.outputs.remove(&pos.into()) {
+            return self.i_plug.outputs.insert(new.into(), i);
+        }
+
+        None
+    }
+
+    fn translate_wplugs(&mut self, offset: Vector2) {
+        let old_pos = self.get_pos();
+        self.translate(offset);
+        let new_pos = self.get_pos();
+
+        for i in 0..self.opts.inputs.len() {
+            self.replace_plug_pos(
+                self.opts.input_plug(old_pos, i),
+                self.opts.input_plug(new_pos, i),
+            );
+        }
+
+        for i in 0..self.opts.outputs.len() {
+            self.replace_plug_pos(
+                self.opts.output_plug(old_pos, i),
+                self.opts.output_plug(new_pos, i),
+            );
+        }
+    }
+
+    // The sub-graph is evaluated like the main graph, each node gets the
+    // outputs linked to its plugs. The exposed inputs get the ones of the
+    // group, and the group gives the output of the node behind its exposed
+    // output, there is only one.
+    fn apply(&mut self, voxmap: &mut VoxelMap, globals: &mut Globals) {
+        let (dims, res) = (voxmap.dims, voxmap.res);
+        let nodes = &self.opts.nodes;
+
+        // only the values of the plugs after the first one are known
+        let blank =
+            NodeOutput::read(&VoxelMap::new(dims, res), &Default::default());
+        let exposed: Vec<Option<Arc<NodeOutput>>> =
+            std::iter::once(Some(Arc::new(NodeOutput::read(voxmap, globals))))
+                .chain(globals.inputs.iter().map(|x| {
+                    x.clone().map(|values| {
+                        Arc::new(NodeOutput {
+                            values,
+                            ..blank.clone()
+                        })
+                    })
+                }))
+                .collect();
+
+        let owner =
+            |p: &Point| nodes.iter().position(|x| x.outputs.contains(p));
+        let mut outputs: Vec<Option<Arc<NodeOutput>>> = vec![None; nodes.len()];
+        let order = self.opts.order();
+
+        for &i in order.iter() {
+            let mut plugs = nodes[i].inputs.clone();
+            plugs.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.total_cmp(&b.0)));
+
+            let inputs = plugs.iter().map(|p| {
+                if let Some((x, _)) =
+                    self.opts.links.iter().find(|(_, y)| y == p)
+                {
+                    return owner(x).and_then(|x| outputs[x].clone());
+                }
+
+                let k = self.opts.inputs.iter().position(|x| x == p)?;
+                exposed.get(k).cloned().flatten()
+            });
+
+            let (output, _) = evaluate(&nodes[i].snapshot(), inputs, dims, res);
+            outputs[i] = Some(Arc::new(output));
+        }
+
+        let last = match self.opts.outputs.first() {
+            Some(p) => owner(p),
+            None => order.last().copied(),
+        };
+
+        if let Some(output) = last.and_then(|x| outputs[x].as_ref()) {
+            output.write(voxmap, globals);
+        }
+    }
+}
+
+impl WidgetConfigurable for Group {
+    fn configure(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        let panel_rect =
+            Rectangle::new(0.0, 0.0, 150.0, globals.dimensions.y + 1.0);
+
+        globals.workspace_locked = panel_rect
+            .check_collision_circle_rec(globals.s_mouse, SELECTION_OFFSET);
+
+        handle.gui_window_box(
+            panel_rect,
+            Some(&CString::new(&*self.id).expect("CString::new failed")),
+        );
+
+        handle.gui_label(
+            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 5.0, 120.0, 20.0),
+            Some(
+                &CString::new(self.opts.name.as_str())
+                    .expect("CString::new failed"),
+            ),
+        );
+
+        handle.gui_label(
+            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 25.0, 120.0, 20.0),
+            Some(
+                &CString::new(format!("Nodes: {}", self.opts.nodes.len()))
+                    .expect("CString::new failed"),
+            ),
+        );
+
+        if handle.gui_button(
+            Rectangle::new(5.0, WINDOW_BOX_TITLE_SIZE + 55.0, 140.0, 30.0),
+            Some(
+                &CString::new("Save to library").expect("CString::new failed"),
+            ),
+        ) {
+            return WidgetSignal::SaveGroup;
+        }
+
+        if handle.gui_button(
+            Rectangle::new(5.0, WINDOW_BOX_TITLE_SIZE + 90.0, 140.0, 30.0),
+            Some(&CString::new("Expand").expect("CString::new failed")),
+        ) {
+            return WidgetSignal::Ungroup;
+        }
+
+        WidgetSignal::None
+    }
+
+    fn get_opts(&self) -> WidgetOpts {
+        WidgetOpts::Group(self.opts.clone())
+    }
+
+    fn set_opts(&mut self, opts: WidgetOpts) -> WidgetSignal {
+        if let WidgetOpts::Group(opts) = opts {
+            self.opts = opts;
+        }
+
+        WidgetSignal::None
+    }
+}
+
+impl Widget for Group {
+    fn render(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        self.draw_plug_links(handle);
+
+        handle.draw_rectangle_rec(self.rect, GROUP_BLOCK_COLOR);
+        handle.draw_rectangle_lines_ex(self.rect, 2, GROUP_BLOCK_BORDER_COLOR);
+
+        handle.draw_text(
+            &self.id,
+            self.rect.x as i32 + 2,
+            self.rect.y as i32 + 5,
+            15,
+            Color::WHITE,
+        );
+
+        handle.draw_text(
+            &self.opts.name,
+            self.rect.x as i32 + 8,
+            self.rect.y as i32 + 22,
+            10,
+            Color::WHITE,
+        );
+
+        self.draw_thumbnail(handle, GROUP_THUMBNAIL_TOP);
+        self.draw_plugs(handle, globals);
+
+        WidgetSignal::None
+    }
+
+    fn call(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        self.render(handle, globals)
+    }
+
+    fn ready(&mut self) {
+        self.ready = true
+    }
+    fn unready(&mut self) {
+        self.ready = false
+    }
+    fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    fn get_type(&self) -> WidgetType {
+        WidgetType::Group
+    }
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+    fn set_id(&mut self, id: String) {
+        self.id = id
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+    fn set_visible(&mut self, state: bool) {
+        self.visible = state
+    }
+    fn show(&mut self) {
+        self.visible = true
+    }
+    fn hide(&mut self) {
+        self.visible = false
+    }
+    fn toggle_visible(&mut self) {
+        self.visible ^= true
+    }
+
+    fn as_widget_rectangle(&self) -> Option<&dyn WidgetRectangle> {
+        Some(self as _)
+    }
+
+    fn as_widget_rectangle_mut(&mut self) -> Option<&mut dyn WidgetRectangle> {
+        Some(self as _)
+    }
+
+    fn as_widget_collidable(&self) -> Option<&dyn WidgetCollidable> {
+        Some(self as _)
+    }
+
+    fn as_widget_collidable_mut(
+        &mut self,
+    ) -> Option<&mut dyn WidgetCollidable> {
+        Some(self as _)
+    }
+
+    fn as_widget_plugable(&self) -> Option<&dyn WidgetPlugable> {
+        Some(self as _)
+    }
+
+    fn as_widget_plugable_mut(&mut self) -> Option<&mut dyn WidgetPlugable> {
+        Some(self as _)
+    }
+
+    fn as_widget_configurable(&self) -> Option<&dyn WidgetConfigurable> {
+        Some(self as _)
+    }
+
+    fn as_widget_configurable_mut(
+        &mut self,
+    ) -> Option<&mut dyn WidgetConfigurable> {
+        Some(self as _)
+    }
+}
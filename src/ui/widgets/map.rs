@@ -44,6 +44,19 @@ impl ObjMap {
             .any(|x| x.check_rect_collision(rect))
     }
 
+    // same as `any_rect_colliding`, the widgets at `ignored` aside
+    pub fn rect_colliding_except(
+        &self,
+        rect: Rectangle,
+        ignored: &[Vector2],
+    ) -> bool {
+        self.objs
+            .data_values()
+            .filter_map(|obj| obj.as_widget_collidable())
+            .filter(|x| !ignored.contains(&x.get_pos()))
+            .any(|x| x.check_rect_collision(rect))
+    }
+
     pub fn find_target(
         &mut self,
         circ: &Circle,
@@ -468,6 +481,153 @@ impl ObjMap {
         )
     }
 
+    // widget linked to every input plug, from the top plug to the bottom one
+    pub fn get_input_sources(&self, pos: Index) -> Option<Vec<Option<Index>>> {
+        let mut plugs = self
+            .objs
+            .data_get(&pos)?
+            .as_widget_plugable()?
+            .inputs()
+            .map(|(x, xvec)| {
+                let source = xvec
+                    .first()
+                    .and_then(|y| self.objs.keys_get(&Vector2::into(*y)));
+
+                (Vec2u::into(*x), source.copied())
+            })
+            .collect::<Vec<(Vector2, Option<Index>)>>();
+
+        plugs.sort_by(|(x, _), (y, _)| {
+            x.y.total_cmp(&y.y).then(x.x.total_cmp(&y.x))
+        });
+
+        Some(plugs.into_iter().map(|(_, x)| x).collect())
+    }
+
+    // inserts the widget at `pos` and aliases its plugs
+    pub fn insert_widget(&mut self, pos: Vector2, widget: Box<dyn Widget>) {
+        let pos: Vec2u = pos.into();
+        let plugs_to_alias: Option<Vec<Vec2u>> = widget
+            .as_widget_plugable()
+            .map(|pl| pl.plug_pos().copied().collect());
+
+        self.objs.insert(pos, widget);
+
+        if let Some(vec) = plugs_to_alias {
+            vec.into_iter().for_each(|x| {
+                self.objs.alias(&pos, x);
+            });
+        }
+    }
+
+    // links of the widget as (output plug, input plug) pairs
+    pub fn widget_links(&self, pos: Vector2) -> Vec<(Vector2, Vector2)> {
+        let plugable = match self
+            .objs
+            .get(&pos.into())
+            .and_then(|x| x.as_widget_plugable())
+        {
+            Some(p) => p,
+            None => return Vec::new(),
+        };
+
+        let inputs = plugable.in_links().flat_map(|(x, xvec)| {
+            xvec.iter().map(move |y| (*y, Vec2u::into(*x)))
+        });
+        let outputs = plugable.out_links().flat_map(|(x, xvec)| {
+            xvec.iter().map(move |y| (Vec2u::into(*x), *y))
+        });
+
+        inputs.chain(outputs).collect()
+    }
+
+    pub fn plug_links(&self, plug_pos: Vector2) -> Vec<(Vector2, Vector2)> {
+        self.widget_links(plug_pos)
+            .into_iter()
+            .filter(|(x, y)| *x == plug_pos || *y == plug_pos)
+            .collect()
+    }
+
+    // removes a single link, the plugs can be given in any order
+    pub fn unlink_widgets(&mut self, from: Vector2, to: Vector2) {
+        for (a, b) in [(from, to), (to, from)] {
+            if let Some(w) = self
+                .objs
+                .get_mut(&a.into())
+                .and_then(|x| x.as_widget_plugable_mut())
+            {
+                let iplug = w.get_iplug_mut();
+                let links = iplug
+                    .inputs
+                    .get_mut(&a.into())
+                    .or_else(|| iplug.outputs.get_mut(&a.into()));
+
+                if let Some(links) = links {
+                    links.retain(|x| *x != b);
+                }
+            }
+        }
+    }
+
+    // replaces the plugs of the widget at `pos` as asked by an IoSwap signal
+    pub fn swap_plugs(
+        &mut self,
+        pos: Vector2,
+        itoadd: Vec<Vector2>,
+        otoadd: Vec<Vector2>,
+        todelete: Vec<Vector2>,
+    ) {
+        todelete.into_iter().for_each(|x| {
+            self.remove_links(x);
+            self.objs.remove_alias(&x.into());
+        });
+
+        let iplug = self
+            .objs
+            .get_mut(&pos.into())
+            .unwrap()
+            .as_widget_plugable_mut()
+            .unwrap()
+            .get_iplug_mut();
+
+        iplug.inputs.clear();
+        iplug.outputs.clear();
+
+        itoadd.iter().for_each(|x| {
+            iplug.inputs.insert((*x).into(), Vec::new());
+        });
+
+        otoadd.iter().for_each(|x| {
+            iplug.outputs.insert((*x).into(), Vec::new());
+        });
+
+        itoadd.into_iter().chain(otoadd).for_each(|x| {
+            self.objs.alias(&pos.into(), x.into());
+        });
+    }
+
+    pub fn get_widget_opts(&self, pos: Vector2) -> WidgetOpts {
+        self.objs
+            .get(&pos.into())
+            .and_then(|x| x.as_widget_configurable())
+            .map_or(WidgetOpts::None, |x| x.get_opts())
+    }
+
+    pub fn set_widget_opts(&mut self, pos: Vector2, opts: WidgetOpts) {
+        let sig = match self
+            .objs
+            .get_mut(&pos.into())
+            .and_then(|x| x.as_widget_configurable_mut())
+        {
+            Some(w) => w.set_opts(opts),
+            None => return,
+        };
+
+        if let WidgetSignal::IoSwap(itoadd, otoadd, todelete) = sig {
+            self.swap_plugs(pos, itoadd, otoadd, todelete);
+        }
+    }
+
     pub fn remove_widget(&mut self, pos: Vector2) -> Option<Box<dyn Widget>> {
         let target = self.objs.remove(&Vector2::into(pos));
 
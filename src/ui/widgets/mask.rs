@@ -0,0 +1,478 @@
+use crate::display::masks::{apply_mask, MaskBlend, MaskOpts, MaskShape};
+use crate::ui::ui::WINDOW_BOX_TITLE_SIZE;
+use crate::widgets::*;
+
+use raylib::prelude::*;
+use std::ffi::CString;
+
+const MASK_BLOCK_COLOR: Color = Color::BEIGE;
+const MASK_BLOCK_BORDER_COLOR: Color = Color::BROWN;
+const MASK_BLOCK_SIZE: f32 = 65.0;
+const MASK_SHAPES: &str = "Radial;Square;Islands;Polygon";
+const POLYGON_EDITOR: Rectangle = Rectangle {
+    x: 15.0,
+    y: WINDOW_BOX_TITLE_SIZE + 195.0,
+    width: 120.0,
+    height: 120.0,
+};
+const I_PLUG_OFFSET: Vector2 = Vector2 {
+    x: 0.0,
+    y: MASK_BLOCK_SIZE / 2.0,
+};
+const O_PLUG_OFFSET: Vector2 = Vector2 {
+    x: MASK_BLOCK_SIZE,
+    y: MASK_BLOCK_SIZE / 2.0,
+};
+
+pub struct Mask {
+    pub opts: MaskOpts,
+
+    pub i_plug: PlugInterface,
+    pub thumbnail: Option<Texture2D>,
+
+    pub rect: Rectangle,
+    pub id: String,
+    pub ready: bool,
+    pub visible: bool,
+}
+
+impl Mask {
+    pub fn create(objmap: &ObjMap, globals: &mut Globals) -> Self {
+        let mut res = Mask::default();
+        let mouse = globals.r_mouse;
+
+        res.rect =
+            Rectangle::new(mouse.x, mouse.y, MASK_BLOCK_SIZE, MASK_BLOCK_SIZE);
+        res.id = format!("{}_{}", res.id, objmap.objs.unique_keys_count());
+
+        let input = mouse + I_PLUG_OFFSET;
+        let output = mouse + O_PLUG_OFFSET;
+
+        res.i_plug = PlugInterface::new(mouse);
+
+        res.i_plug.inputs.insert(input.into(), Vec::new());
+        res.i_plug.outputs.insert(output.into(), Vec::new());
+
+        res
+    }
+}
+
+impl Default for Mask {
+    fn default() -> Self {
+        Self {
+            opts: MaskOpts::default(),
+            i_plug: PlugInterface::default(),
+            thumbnail: None,
+
+            rect: Rectangle::EMPTY,
+            id: String::from("Mask"),
+            ready: false,
+            visible: true,
+        }
+    }
+}
+
+impl WidgetRectangle for Mask {
+    fn get_rect(&self) -> Rectangle {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rectangle) {
+        self.rect = rect;
+    }
+}
+
+impl WidgetCollidable for Mask {}
+
+impl WidgetPlugable for Mask {
+    fn get_iplug(&self) -> &PlugInterface {
+        &self.i_plug
+    }
+
+    fn get_iplug_mut(&mut self) -> &mut PlugInterface {
+        &mut self.i_plug
+    }
+
+    fn get_thumbnail(&self) -> Option<&Texture2D> {
+        self.thumbnail.as_ref()
+    }
+
+    fn set_thumbnail(&mut self, thumbnail: Texture2D) {
+        self.thumbnail = Some(thumbnail);
+    }
+
+    fn replace_plug_pos(
+        &mut self,
+        pos: Vector2,
+        new: Vector2,
+    ) -> Option<Vec<Vector2>> {
+        if let Some(i) = self.i_plug.inputs.remove(&pos.into()) {
+            if let Some(c) = self.i_plug.inputs.insert(new.into(), i) {
+                return Some(c);
+            }
+
+            return None;
+        }
+
+        if let Some(i) = self.i_plug.outputs.remove(&pos.into()) {
+            if let Some(c) = self.i_plug.outputs.insert(new.into(), i) {
+                return Some(c);
+            }
+
+            return None;
+        }
+
+        None
+    }
+
+    fn translate_wplugs(&mut self, offset: Vector2) {
+        let old_pos = self.get_pos();
+        self.translate(offset);
+        let new_pos = self.get_pos();
+        self.replace_plug_pos(old_pos + I_PLUG_OFFSET, new_pos + I_PLUG_OFFSET);
+        self.replace_plug_pos(old_pos + O_PLUG_OFFSET, new_pos + O_PLUG_OFFSET);
+    }
+
+    fn apply(&mut self, voxmap: &mut VoxelMap, _: &mut Globals) {
+        let mut base =
+            vec![vec![0.0; voxmap.dims.x as usize]; voxmap.dims.y as usize];
+
+        voxmap.voxels.iter().for_each(|x| {
+            base[x.coords.x as usize][x.coords.y as usize] = x.value;
+        });
+
+        apply_mask(&mut base, &self.opts);
+
+        voxmap.voxels.iter_mut().for_each(|x| {
+            let cx = x.coords.x as usize;
+            let cy = x.coords.y as usize;
+            x.value = base[cx][cy];
+        });
+    }
+}
+
+impl WidgetConfigurable for Mask {
+    fn configure(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        let panel_rect =
+            Rectangle::new(0.0, 0.0, 150.0, globals.dimensions.y + 1.0);
+
+        globals.workspace_locked = panel_rect
+            .check_collision_circle_rec(globals.s_mouse, SELECTION_OFFSET);
+
+        handle.gui_window_box(
+            panel_rect,
+            Some(&CString::new(&*self.id).expect("CString::new failed")),
+        );
+
+        let shape = handle.gui_combo_box(
+            Rectangle::new(5.0, WINDOW_BOX_TITLE_SIZE + 5.0, 140.0, 30.0),
+            Some(&CString::new(MASK_SHAPES).expect("CString::new failed")),
+            self.opts.shape as i32,
+        );
+
+        self.opts.shape = match shape {
+            1 => MaskShape::Square,
+            2 => MaskShape::MultiIsland,
+            3 => MaskShape::Polygon,
+            _ => MaskShape::Radial,
+        };
+
+        let blend = handle.gui_toggle(
+            Rectangle::new(5.0, WINDOW_BOX_TITLE_SIZE + 40.0, 140.0, 30.0),
+            Some(&CString::new("blend mode").expect("CString::new failed")),
+            self.opts.blend == MaskBlend::Blend,
+        );
+
+        self.opts.blend = if blend {
+            MaskBlend::Blend
+        } else {
+            MaskBlend::Multiply
+        };
+
+        handle.gui_group_box(
+            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 80.0, 120.0, 20.0),
+            Some(
+                &CString::new(format!("Radius: {}", self.opts.radius))
+                    .expect("CString::new failed"),
+            ),
+        );
+
+        self.opts.radius = ((handle.gui_slider_bar(
+            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 87.0, 120.0, 20.0),
+            Some(&CString::new("0").expect("CString::new failed")),
+            Some(&CString::new("1").expect("CString::new failed")),
+            self.opts.radius as f32,
+            0.0,
+            1.0,
+        ) * 100.0)
+            .round()
+            / 100.0) as f64;
+
+        handle.gui_group_box(
+            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 117.0, 120.0, 20.0),
+            Some(
+                &CString::new(format!("Falloff: {}", self.opts.falloff))
+                    .expect("CString::new failed"),
+            ),
+        );
+
+        self.opts.falloff = ((handle.gui_slider_bar(
+            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 124.0, 120.0, 20.0),
+            Some(&CString::new("0").expect("CString::new failed")),
+            Some(&CString::new("1").expect("CString::new failed")),
+            self.opts.falloff as f32,
+            0.0,
+            1.0,
+        ) * 100.0)
+            .round()
+            / 100.0) as f64;
+
+        handle.gui_group_box(
+            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 154.0, 120.0, 20.0),
+            Some(
+                &CString::new(format!("Strength: {}", self.opts.strength))
+                    .expect("CString::new failed"),
+            ),
+        );
+
+        self.opts.strength = ((handle.gui_slider_bar(
+            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 161.0, 120.0, 20.0),
+            Some(&CString::new("0").expect("CString::new failed")),
+            Some(&CString::new("1").expect("CString::new failed")),
+            self.opts.strength as f32,
+            0.0,
+            1.0,
+        ) * 100.0)
+            .round()
+            / 100.0) as f64;
+
+        match self.opts.shape {
+            MaskShape::MultiIsland => {
+                handle.gui_group_box(
+                    Rectangle::new(
+                        15.0,
+                        WINDOW_BOX_TITLE_SIZE + 195.0,
+                        120.0,
+                        20.0,
+                    ),
+                    Some(
+                        &CString::new(format!(
+                            "Islands: {}",
+                            self.opts.islands
+                        ))
+                        .expect("CString::new failed"),
+                    ),
+                );
+
+                self.opts.islands = handle
+                    .gui_slider_bar(
+                        Rectangle::new(
+                            15.0,
+                            WINDOW_BOX_TITLE_SIZE + 202.0,
+                            120.0,
+                            20.0,
+                        ),
+                        Some(&CString::new("1").expect("CString::new failed")),
+                        Some(&CString::new("10").expect("CString::new failed")),
+                        self.opts.islands as f32,
+                        1.0,
+                        10.0,
+                    )
+                    .round() as usize;
+
+                if handle.gui_button(
+                    Rectangle::new(
+                        5.0,
+                        WINDOW_BOX_TITLE_SIZE + 232.0,
+                        140.0,
+                        30.0,
+                    ),
+                    Some(&CString::new("Reseed").expect("CString::new failed")),
+                ) {
+                    self.opts.seed = self.opts.seed.wrapping_add(1);
+                }
+            }
+            MaskShape::Polygon => {
+                handle.draw_rectangle_rec(POLYGON_EDITOR, Color::SKYBLUE);
+                handle.draw_rectangle_lines_ex(POLYGON_EDITOR, 1, Color::GRAY);
+
+                let to_editor = |(x, y): (f64, f64)| {
+                    Vector2::new(
+                        POLYGON_EDITOR.x + x as f32 * POLYGON_EDITOR.width,
+                        POLYGON_EDITOR.y + y as f32 * POLYGON_EDITOR.height,
+                    )
+                };
+
+                let points = &self.opts.points;
+                for (i, p) in points.iter().enumerate() {
+                    let next = points[(i + 1) % points.len()];
+                    handle.draw_line_v(
+                        to_editor(*p),
+                        to_editor(next),
+                        Color::BROWN,
+                    );
+                    handle.draw_circle_v(to_editor(*p), 2.0, Color::DARKBROWN);
+                }
+
+                if handle
+                    .is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON)
+                    && POLYGON_EDITOR.check_collision_point_rec(globals.s_mouse)
+                {
+                    self.opts.points.push((
+                        ((globals.s_mouse.x - POLYGON_EDITOR.x)
+                            / POLYGON_EDITOR.width)
+                            as f64,
+                        ((globals.s_mouse.y - POLYGON_EDITOR.y)
+                            / POLYGON_EDITOR.height)
+                            as f64,
+                    ));
+                }
+
+                if handle.gui_button(
+                    Rectangle::new(
+                        5.0,
+                        WINDOW_BOX_TITLE_SIZE + 325.0,
+                        140.0,
+                        30.0,
+                    ),
+                    Some(&CString::new("Clear").expect("CString::new failed")),
+                ) {
+                    self.opts.points.clear();
+                }
+            }
+            _ => {}
+        }
+
+        if handle.gui_button(
+            Rectangle::new(5.0, globals.dimensions.y - 35.0, 140.0, 30.0),
+            Some(&CString::new("Reset").expect("CString::new failed")),
+        ) {
+            self.opts = MaskOpts::default();
+        }
+
+        WidgetSignal::None
+    }
+
+    fn get_opts(&self) -> WidgetOpts {
+        WidgetOpts::Mask(self.opts.clone())
+    }
+
+    fn set_opts(&mut self, opts: WidgetOpts) -> WidgetSignal {
+        if let WidgetOpts::Mask(opts) = opts {
+            self.opts = opts;
+        }
+
+        WidgetSignal::None
+    }
+}
+
+impl Widget for Mask {
+    fn render(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        self.draw_plug_links(handle);
+
+        handle.draw_rectangle_rec(self.rect, MASK_BLOCK_COLOR);
+        handle.draw_rectangle_lines_ex(self.rect, 2, MASK_BLOCK_BORDER_COLOR);
+
+        handle.draw_text(
+            &self.id,
+            self.rect.x as i32 + 2,
+            self.rect.y as i32 + 5,
+            15,
+            Color::WHITE,
+        );
+
+        self.draw_thumbnail(handle, THUMBNAIL_TOP);
+        self.draw_plugs(handle, globals);
+
+        WidgetSignal::None
+    }
+
+    fn call(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        self.render(handle, globals)
+    }
+
+    fn ready(&mut self) {
+        self.ready = true
+    }
+    fn unready(&mut self) {
+        self.ready = false
+    }
+    fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    fn get_type(&self) -> WidgetType {
+        WidgetType::Mask
+    }
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+    fn set_id(&mut self, id: String) {
+        self.id = id
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+    fn set_visible(&mut self, state: bool) {
+        self.visible = state
+    }
+    fn show(&mut self) {
+        self.visible = true
+    }
+    fn hide(&mut self) {
+        self.visible = false
+    }
+    fn toggle_visible(&mut self) {
+        self.visible ^= true
+    }
+
+    fn as_widget_rectangle(&self) -> Option<&dyn WidgetRectangle> {
+        Some(self as _)
+    }
+
+    fn as_widget_rectangle_mut(&mut self) -> Option<&mut dyn WidgetRectangle> {
+        Some(self as _)
+    }
+
+    fn as_widget_collidable(&self) -> Option<&dyn WidgetCollidable> {
+        Some(self as _)
+    }
+
+    fn as_widget_collidable_mut(
+        &mut self,
+    ) -> Option<&mut dyn WidgetCollidable> {
+        Some(self as _)
+    }
+
+    fn as_widget_plugable(&self) -> Option<&dyn WidgetPlugable> {
+        Some(self as _)
+    }
+
+    fn as_widget_plugable_mut(&mut self) -> Option<&mut dyn WidgetPlugable> {
+        Some(self as _)
+    }
+
+    fn as_widget_configurable(&self) -> Option<&dyn WidgetConfigurable> {
+        Some(self as _)
+    }
+
+    fn as_widget_configurable_mut(
+        &mut self,
+    ) -> Option<&mut dyn WidgetConfigurable> {
+        Some(self as _)
+    }
+}
@@ -1,19 +1,34 @@
 // pub mod button;
 // pub mod counter;
+pub mod analysis;
 pub mod coloration;
 pub mod dsquare;
+pub mod group;
 pub mod map;
+pub mod mask;
+pub mod number_box;
 pub mod perlin;
 pub mod previewer;
+pub mod roads;
+pub mod settlements;
+pub mod viewer;
 pub mod widget_chooser;
 pub mod widget_io;
 
 // use crate::ieef64::Vec2f64;
+use crate::diamond_square::diamond_square::DsquareOpts;
+use crate::display::masks::MaskOpts;
+use crate::display::settlements::{RoadOpts, SettlementOpts};
 use crate::maths::{Circle, Vec2u};
+use crate::perlin_noise::perlin::PerlinOpts;
 use crate::ui::ui::Globals;
 use map::*;
 // use raylib::core::collision::check_collision_circles;
+use analysis::AnalysisOpts;
+use coloration::ColorationOpts;
+use group::GroupOpts;
 use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use super::ui::{
@@ -21,6 +36,11 @@ use super::ui::{
 };
 use super::voxels::VoxelMap;
 
+// offset of the thumbnail from the top of the block, below the id
+pub const THUMBNAIL_TOP: f32 = 22.0;
+const THUMBNAIL_MARGIN: f32 = 8.0;
+const THUMBNAIL_MIN_SIZE: f32 = 12.0;
+
 #[derive(Clone)]
 pub enum WidgetSignal {
     None,
@@ -30,15 +50,39 @@ pub enum WidgetSignal {
     Vec(Vec<WidgetSignal>),
     //IoSwap(InputToAdd,OutputToAdd, ToDelete)
     IoSwap(Vec<Vector2>, Vec<Vector2>, Vec<Vector2>),
+    // buttons of the group panel, they act on the whole graph
+    SaveGroup,
+    Ungroup,
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum WidgetType {
     None,
     Io,
     Perlin,
     Dsquare,
     Coloration,
+    Mask,
+    Analysis,
+    Group,
+    Settlements,
+    Roads,
+}
+
+// options edited in the configure panels, the history keeps them to undo
+// the edits, and the library to save groups
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WidgetOpts {
+    None,
+    Io(bool),
+    Perlin(PerlinOpts),
+    Dsquare(DsquareOpts),
+    Coloration(ColorationOpts),
+    Mask(MaskOpts),
+    Analysis(AnalysisOpts),
+    Group(GroupOpts),
+    Settlements(SettlementOpts),
+    Roads(RoadOpts),
 }
 
 #[derive(Clone, Default)]
@@ -284,7 +328,58 @@ pub trait WidgetPlugable: WidgetCollidable {
         self.draw_output_plugs(handle, globals);
     }
 
+    fn get_thumbnail(&self) -> Option<&Texture2D> {
+        None
+    }
+
+    fn set_thumbnail(&mut self, _thumbnail: Texture2D) {}
+
+    // last output of the node, in the largest square fitting below `top`
+    fn draw_thumbnail(
+        &self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        top: f32,
+    ) {
+        let tex = match self.get_thumbnail() {
+            Some(tex) => tex,
+            None => return,
+        };
+
+        let rect = self.get_rect();
+        let size = (rect.width - THUMBNAIL_MARGIN * 2.0)
+            .min(rect.height - top - THUMBNAIL_MARGIN);
+
+        if size < THUMBNAIL_MIN_SIZE {
+            return;
+        }
+
+        let dest = Rectangle::new(
+            rect.x + (rect.width - size) / 2.0,
+            rect.y + top,
+            size,
+            size,
+        );
+
+        handle.draw_texture_pro(
+            tex,
+            Rectangle::new(0.0, 0.0, tex.width as f32, tex.height as f32),
+            dest,
+            Vector2::zero(),
+            0.0,
+            Color::WHITE,
+        );
+        handle.draw_rectangle_lines_ex(dest, 1, Color::DARKGRAY);
+    }
+
     fn apply(&mut self, voxmap: &mut VoxelMap, globals: &mut Globals);
+
+    // data kept by `apply` besides its output, moved between the copy
+    // evaluated in the background and the widget of the graph
+    fn take_grid(&mut self) -> Vec<Vec<f64>> {
+        Vec::new()
+    }
+
+    fn set_grid(&mut self, _grid: Vec<Vec<f64>>) {}
 }
 
 impl std::fmt::Display for &dyn WidgetPlugable {
@@ -305,6 +400,15 @@ pub trait WidgetConfigurable: WidgetCollidable {
         handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
         globals: &mut Globals,
     ) -> WidgetSignal;
+
+    fn get_opts(&self) -> WidgetOpts {
+        WidgetOpts::None
+    }
+
+    // the signal has to be handled like the one returned by `configure`
+    fn set_opts(&mut self, _opts: WidgetOpts) -> WidgetSignal {
+        WidgetSignal::None
+    }
 }
 
 pub trait Widget {
@@ -0,0 +1,119 @@
+// Numeric text boxes of the configure panels. The text being typed is kept
+// in the globals, the keyboard shortcuts are disabled meanwhile.
+
+use crate::ui::ui::Globals;
+
+use raylib::prelude::*;
+use std::ffi::CString;
+use std::fmt::Display;
+use std::str::FromStr;
+
+const TEXT_CAPACITY: usize = 32;
+const SLIDER_WIDTH: f32 = 78.0;
+const BOX_WIDTH: f32 = 37.0;
+
+pub struct TextEdit {
+    // id of the widget whose panel holds the box
+    owner: String,
+    field: usize,
+    text: Vec<u8>,
+}
+
+fn to_text(value: impl Display) -> Vec<u8> {
+    let mut text = value.to_string().into_bytes();
+    text.resize(TEXT_CAPACITY, 0);
+    text
+}
+
+fn from_text<T: FromStr>(text: &[u8]) -> Option<T> {
+    let end = text.iter().position(|x| *x == 0).unwrap_or(text.len());
+
+    std::str::from_utf8(&text[..end]).ok()?.trim().parse().ok()
+}
+
+// Text box showing `value`, clicking it starts the edition. Returns the
+// typed number once enter is pressed or the box is left. The key is the id
+// of the widget owning the panel and the number of the box in the panel.
+pub fn number_box<T: Display + FromStr>(
+    handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+    globals: &mut Globals,
+    (owner, field): (&str, usize),
+    bounds: Rectangle,
+    value: T,
+) -> Option<T> {
+    // left over by a panel that was closed
+    if matches!(&globals.text_edit, Some(x) if x.owner != owner) {
+        globals.text_edit = None;
+    }
+
+    let editing = matches!(&globals.text_edit, Some(x) if x.field == field);
+    let mut text = match &globals.text_edit {
+        Some(edit) if editing => edit.text.clone(),
+        _ => to_text(value),
+    };
+
+    let pressed = handle.gui_text_box(bounds, &mut text, editing);
+
+    if !editing {
+        if pressed {
+            globals.text_edit = Some(TextEdit {
+                owner: String::from(owner),
+                field,
+                text,
+            });
+        }
+
+        return None;
+    }
+
+    if !pressed {
+        globals.text_edit = Some(TextEdit {
+            owner: String::from(owner),
+            field,
+            text,
+        });
+
+        return None;
+    }
+
+    globals.text_edit = None;
+    from_text(&text)
+}
+
+// Titled slider with a number box on its right, the typed value is clamped
+// to the range of the slider.
+pub fn number_slider(
+    handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+    globals: &mut Globals,
+    key: (&str, usize),
+    pos: Vector2,
+    title: &str,
+    value: f32,
+    range: (f32, f32),
+) -> f32 {
+    handle.gui_group_box(
+        Rectangle::new(pos.x, pos.y, SLIDER_WIDTH + BOX_WIDTH + 5.0, 20.0),
+        Some(&CString::new(title).expect("CString::new failed")),
+    );
+
+    let value = handle.gui_slider_bar(
+        Rectangle::new(pos.x, pos.y + 7.0, SLIDER_WIDTH, 20.0),
+        None,
+        None,
+        value,
+        range.0,
+        range.1,
+    );
+
+    let bounds = Rectangle::new(
+        pos.x + SLIDER_WIDTH + 5.0,
+        pos.y + 7.0,
+        BOX_WIDTH,
+        20.0,
+    );
+
+    match number_box(handle, globals, key, bounds, value) {
+        Some(typed) => typed.clamp(range.0, range.1),
+        None => value,
+    }
+}
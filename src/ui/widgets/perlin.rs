@@ -1,9 +1,9 @@
 use crate::perlin_noise::perlin::{self, *};
 use crate::ui::ui::WINDOW_BOX_TITLE_SIZE;
 // use crate::voxels::*;
+use crate::widgets::number_box::*;
 use crate::widgets::*;
 
-use rand::distributions::{Distribution, Uniform};
 use rand::prelude::*;
 use raylib::prelude::*;
 use std::ffi::CString;
@@ -24,6 +24,7 @@ pub struct Perlin {
     pub opts: PerlinOpts,
 
     pub i_plug: PlugInterface,
+    pub thumbnail: Option<Texture2D>,
 
     pub rect: Rectangle,
     pub id: String,
@@ -54,32 +55,18 @@ impl Perlin {
 
         res
     }
-
-    fn gen_permutations(&mut self) {
-        self.opts.permutations = [0; 512];
-        let mut rng = rand::rngs::StdRng::from_entropy();
-        // println!("{}", rng.gen::<f32>());
-
-        for i in 0..256 {
-            self.opts.permutations[i] = i;
-        }
-
-        for i in 0..256 {
-            let j = Uniform::from(0..256).sample(&mut rng) & 0xFF;
-            self.opts.permutations.swap(i, j);
-        }
-
-        for i in 0..256 {
-            self.opts.permutations[i + 256] = self.opts.permutations[i];
-        }
-    }
 }
 
 impl Default for Perlin {
     fn default() -> Self {
         Self {
-            opts: PerlinOpts::default(),
+            // a new node gives a new noise
+            opts: PerlinOpts {
+                seed: rand::random(),
+                ..PerlinOpts::default()
+            },
             i_plug: PlugInterface::default(),
+            thumbnail: None,
 
             rect: Rectangle::EMPTY,
             id: String::from("Perlin"),
@@ -110,6 +97,14 @@ impl WidgetPlugable for Perlin {
         &mut self.i_plug
     }
 
+    fn get_thumbnail(&self) -> Option<&Texture2D> {
+        self.thumbnail.as_ref()
+    }
+
+    fn set_thumbnail(&mut self, thumbnail: Texture2D) {
+        self.thumbnail = Some(thumbnail);
+    }
+
     fn replace_plug_pos(
         &mut self,
         pos: Vector2,
@@ -143,14 +138,16 @@ impl WidgetPlugable for Perlin {
     }
 
     fn apply(&mut self, voxmap: &mut VoxelMap, _: &mut Globals) {
-        self.gen_permutations();
+        let mut opts = self.opts.clone();
+        opts.permutations = perlin::seeded_permutations(self.opts.seed);
 
         for vox in voxmap.voxels.iter_mut() {
-            self.opts.x = vox.coords.x / voxmap.dims.x * voxmap.res.x;
-            self.opts.y = vox.coords.y / voxmap.dims.y * voxmap.res.y;
-            self.opts.z = 1.0;
-            self.opts.base = 0.0;
-            vox.value = perlin::perlin3d(&self.opts);
+            let x = vox.coords.x / voxmap.dims.x * voxmap.res.x;
+            let y = vox.coords.y / voxmap.dims.y * voxmap.res.y;
+
+            opts.x = x * opts.frequency + opts.offset_x;
+            opts.y = y * opts.frequency + opts.offset_y;
+            vox.value = perlin::perlin3d(&opts);
         }
     }
 }
@@ -172,68 +169,151 @@ impl WidgetConfigurable for Perlin {
             Some(&CString::new(&*self.id).expect("CString::new failed")),
         );
 
-        handle.gui_group_box(
-            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 10.0, 120.0, 20.0),
-            Some(
-                &CString::new(format!("Octaves: {}", self.opts.octaves))
-                    .expect("CString::new failed"),
-            ),
-        );
-
-        self.opts.octaves = handle
-            .gui_slider_bar(
-                Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 17.0, 120.0, 20.0),
-                Some(&CString::new("1").expect("CString::new failed")),
-                Some(&CString::new("10").expect("CString::new failed")),
-                self.opts.octaves as f32,
-                1.0,
-                10.0,
-            )
-            .ceil() as usize;
-
-        handle.gui_group_box(
-            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 47.0, 120.0, 20.0),
-            Some(
-                &CString::new(format!("Lacunarity: {}", self.opts.lacunarity))
-                    .expect("CString::new failed"),
-            ),
-        );
-
-        self.opts.lacunarity = ((handle.gui_slider_bar(
-            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 54.0, 120.0, 20.0),
-            Some(&CString::new("1").expect("CString::new failed")),
-            Some(&CString::new("5").expect("CString::new failed")),
+        let id = self.id.clone();
+        let row = |i: usize| {
+            Vector2::new(15.0, WINDOW_BOX_TITLE_SIZE + 10.0 + 37.0 * i as f32)
+        };
+
+        self.opts.octaves = number_slider(
+            handle,
+            globals,
+            (&id, 0),
+            row(0),
+            "Octaves",
+            self.opts.octaves as f32,
+            (1.0, 10.0),
+        )
+        .ceil() as usize;
+
+        self.opts.lacunarity = ((number_slider(
+            handle,
+            globals,
+            (&id, 1),
+            row(1),
+            "Lacunarity",
             self.opts.lacunarity as f32,
-            1.0,
-            5.0,
+            (1.0, 5.0),
         ) * 10.0)
             .ceil()
             / 10.0) as f64;
 
-        handle.gui_group_box(
-            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 84.0, 120.0, 20.0),
-            Some(
-                &CString::new(format!("Fallout: {}", self.opts.fallout))
-                    .expect("CString::new failed"),
-            ),
-        );
-
-        self.opts.fallout = ((handle.gui_slider_bar(
-            Rectangle::new(15.0, WINDOW_BOX_TITLE_SIZE + 91.0, 120.0, 20.0),
-            Some(&CString::new("0").expect("CString::new failed")),
-            Some(&CString::new("1").expect("CString::new failed")),
+        self.opts.fallout = ((number_slider(
+            handle,
+            globals,
+            (&id, 2),
+            row(2),
+            "Fallout",
             self.opts.fallout as f32,
-            0.0,
-            1.0,
+            (0.0, 1.0),
         ) * 10000.0)
             .ceil()
             / 10000.0) as f64;
 
+        self.opts.frequency = (number_slider(
+            handle,
+            globals,
+            (&id, 3),
+            row(3),
+            "Frequency",
+            self.opts.frequency,
+            (0.1, 10.0),
+        ) * 100.0)
+            .round()
+            / 100.0;
+
+        self.opts.offset_x = (number_slider(
+            handle,
+            globals,
+            (&id, 4),
+            row(4),
+            "Offset X",
+            self.opts.offset_x,
+            (-10.0, 10.0),
+        ) * 100.0)
+            .round()
+            / 100.0;
+
+        self.opts.offset_y = (number_slider(
+            handle,
+            globals,
+            (&id, 5),
+            row(5),
+            "Offset Y",
+            self.opts.offset_y,
+            (-10.0, 10.0),
+        ) * 100.0)
+            .round()
+            / 100.0;
+
+        self.opts.z = (number_slider(
+            handle,
+            globals,
+            (&id, 6),
+            row(6),
+            "Z",
+            self.opts.z,
+            (0.0, 10.0),
+        ) * 100.0)
+            .round()
+            / 100.0;
+
+        self.opts.base = ((number_slider(
+            handle,
+            globals,
+            (&id, 7),
+            row(7),
+            "Base",
+            self.opts.base as f32,
+            (-1.0, 1.0),
+        ) * 100.0)
+            .round()
+            / 100.0) as f64;
+
+        let seed = row(8);
+
+        handle.gui_group_box(
+            Rectangle::new(seed.x, seed.y, 120.0, 20.0),
+            Some(&CString::new("Seed").expect("CString::new failed")),
+        );
+
+        if let Some(typed) = number_box(
+            handle,
+            globals,
+            (&id, 8),
+            Rectangle::new(seed.x, seed.y + 7.0, 120.0, 20.0),
+            self.opts.seed,
+        ) {
+            self.opts.seed = typed;
+        }
+
+        if handle.gui_button(
+            Rectangle::new(seed.x, seed.y + 32.0, 120.0, 20.0),
+            Some(&CString::new("Randomize").expect("CString::new failed")),
+        ) {
+            self.opts.seed = rand::random();
+        }
+
         if handle.gui_button(
             Rectangle::new(5.0, globals.dimensions.y - 35.0, 140.0, 30.0),
             Some(&CString::new("Reset").expect("CString::new failed")),
         ) {
-            self.opts = PerlinOpts::default();
+            // the seed has its own button
+            self.opts = PerlinOpts {
+                seed: self.opts.seed,
+                ..PerlinOpts::default()
+            };
+        }
+
+        WidgetSignal::None
+    }
+
+    fn get_opts(&self) -> WidgetOpts {
+        WidgetOpts::Perlin(self.opts.clone())
+    }
+
+    fn set_opts(&mut self, opts: WidgetOpts) -> WidgetSignal {
+        if let WidgetOpts::Perlin(opts) = opts {
+            self.opts = opts;
         }
 
         WidgetSignal::None
@@ -259,6 +339,7 @@ impl Widget for Perlin {
             Color::WHITE,
         );
 
+        self.draw_thumbnail(handle, THUMBNAIL_TOP);
         self.draw_plugs(handle, globals);
 
         WidgetSignal::None
@@ -1,3 +1,4 @@
+use crate::display::hillshade::{hillshade, HillshadeOpts};
 use crate::ui::ui::SELECTION_OFFSET;
 use crate::voxels::*;
 use crate::widgets::*;
@@ -5,11 +6,17 @@ use raylib::prelude::*;
 
 use std::ffi::CString;
 
+const SHADING_PANEL_HEIGHT: f32 = 80.0;
+
 pub struct Previewer {
     pub voxmap: VoxelMap,
     pub scroll: Vector2,
     pub callback: fn(&mut Self, &WidgetSignal),
     pub texture: Option<Texture2D>,
+    pub shading: HillshadeOpts,
+    pub shaded: bool,
+    pub shading_changed: bool,
+    pub light: Option<Vec<Vec<f64>>>,
 
     pub rect: Rectangle,
     pub id: String,
@@ -23,7 +30,7 @@ impl Previewer {
         handle: &mut RaylibHandle,
         rl_thread: &RaylibThread,
     ) {
-        let img = self.voxmap.render_to_img();
+        let img = self.voxmap.render_to_img(self.light.as_ref());
         let tex = handle.load_texture_from_image(rl_thread, &img);
 
         self.texture = match tex {
@@ -31,6 +38,86 @@ impl Previewer {
             _ => None,
         }
     }
+
+    // biome maps stand for the altitude they were computed from
+    pub fn heights(&self, altmap: &Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        if self.voxmap.biomes && !altmap.is_empty() {
+            altmap.clone()
+        } else {
+            self.voxmap.heights()
+        }
+    }
+
+    pub fn update_shading(&mut self, altmap: &Vec<Vec<f64>>) {
+        self.shading_changed = false;
+
+        if !self.shaded {
+            self.light = None;
+            return;
+        }
+
+        self.light = Some(hillshade(&self.heights(altmap), &self.shading));
+    }
+
+    fn draw_shading_panel(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+    ) {
+        let x = self.rect.x + 70.0;
+        let y = self.rect.y + self.rect.height + 5.0;
+        let old = self.shading.clone();
+
+        handle.draw_rectangle_rec(
+            Rectangle::new(
+                self.rect.x,
+                y - 5.0,
+                self.rect.width,
+                SHADING_PANEL_HEIGHT,
+            ),
+            Color::RAYWHITE,
+        );
+
+        self.shading.azimuth = handle
+            .gui_slider_bar(
+                Rectangle::new(x, y, 150.0, 20.0),
+                Some(&CString::new("Azimuth").expect("CString::new failed")),
+                Some(
+                    &CString::new(format!("{}", self.shading.azimuth))
+                        .expect("CString::new failed"),
+                ),
+                self.shading.azimuth as f32,
+                0.0,
+                360.0,
+            )
+            .round() as f64;
+
+        self.shading.elevation = handle
+            .gui_slider_bar(
+                Rectangle::new(x, y + 25.0, 150.0, 20.0),
+                Some(&CString::new("Elevation").expect("CString::new failed")),
+                Some(
+                    &CString::new(format!("{}", self.shading.elevation))
+                        .expect("CString::new failed"),
+                ),
+                self.shading.elevation as f32,
+                5.0,
+                90.0,
+            )
+            .round() as f64;
+
+        self.shading.ambient_occlusion = handle.gui_toggle(
+            Rectangle::new(x, y + 50.0, 150.0, 20.0),
+            Some(
+                &CString::new("ambient occlusion")
+                    .expect("CString::new failed"),
+            ),
+            self.shading.ambient_occlusion,
+        );
+
+        if self.shading != old {
+            self.shading_changed = true;
+        }
+    }
 }
 
 impl Default for Previewer {
@@ -52,6 +139,10 @@ impl Default for Previewer {
                 }
             },
             texture: None,
+            shading: HillshadeOpts::default(),
+            shaded: false,
+            shading_changed: false,
+            light: None,
 
             rect: Rectangle::new(250.0, 40.0, 524.0, 547.0),
             id: String::from("Preview Window"),
@@ -80,8 +171,12 @@ impl Widget for Previewer {
         globals: &mut Globals,
     ) -> WidgetSignal {
         if self.visible {
-            globals.workspace_locked = self
-                .rect
+            let mut locked_rect = self.rect;
+            if self.shaded {
+                locked_rect.height += SHADING_PANEL_HEIGHT;
+            }
+
+            globals.workspace_locked = locked_rect
                 .check_collision_circle_rec(globals.r_mouse, SELECTION_OFFSET);
 
             let mut res = vec![WidgetSignal::None, WidgetSignal::None];
@@ -141,6 +236,10 @@ impl Widget for Previewer {
 
             res[1] = WidgetSignal::Vector2(scroll);
 
+            if self.shaded {
+                self.draw_shading_panel(handle);
+            }
+
             if let Some(tex) = &self.texture {
                 handle.draw_texture_rec(
                     tex,
@@ -0,0 +1,348 @@
+use crate::display::settlements::*;
+use crate::ui::ui::WINDOW_BOX_TITLE_SIZE;
+use crate::widgets::number_box::*;
+use crate::widgets::settlements::{biome_grid, biome_toggles, write_grid};
+use crate::widgets::*;
+
+use raylib::prelude::*;
+use std::ffi::CString;
+
+const ROADS_BLOCK_COLOR: Color = Color::DARKGRAY;
+const ROADS_BLOCK_BORDER_COLOR: Color = Color::BLACK;
+const ROADS_BLOCK_SIZE: f32 = 65.0;
+const I_PLUG_OFFSET: Vector2 = Vector2 {
+    x: 0.0,
+    y: ROADS_BLOCK_SIZE / 2.0,
+};
+const O_PLUG_OFFSET: Vector2 = Vector2 {
+    x: ROADS_BLOCK_SIZE,
+    y: ROADS_BLOCK_SIZE / 2.0,
+};
+
+pub struct Roads {
+    pub opts: RoadOpts,
+
+    pub i_plug: PlugInterface,
+    pub thumbnail: Option<Texture2D>,
+
+    pub rect: Rectangle,
+    pub id: String,
+    pub ready: bool,
+    pub visible: bool,
+}
+
+impl Roads {
+    pub fn create(objmap: &ObjMap, globals: &mut Globals) -> Self {
+        let mut res = Roads::default();
+        let mouse = globals.r_mouse;
+
+        res.rect = Rectangle::new(
+            mouse.x,
+            mouse.y,
+            ROADS_BLOCK_SIZE,
+            ROADS_BLOCK_SIZE,
+        );
+        res.id = format!("{}_{}", res.id, objmap.objs.unique_keys_count());
+
+        let input = mouse + I_PLUG_OFFSET;
+        let output = mouse + O_PLUG_OFFSET;
+
+        res.i_plug = PlugInterface::new(mouse);
+
+        res.i_plug.inputs.insert(input.into(), Vec::new());
+        res.i_plug.outputs.insert(output.into(), Vec::new());
+
+        res
+    }
+}
+
+impl Default for Roads {
+    fn default() -> Self {
+        Self {
+            opts: RoadOpts::default(),
+            i_plug: PlugInterface::default(),
+            thumbnail: None,
+
+            rect: Rectangle::EMPTY,
+            id: String::from("Roads"),
+            ready: false,
+            visible: true,
+        }
+    }
+}
+
+impl WidgetRectangle for Roads {
+    fn get_rect(&self) -> Rectangle {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rectangle) {
+        self.rect = rect;
+    }
+}
+
+impl WidgetCollidable for Roads {}
+
+impl WidgetPlugable for Roads {
+    fn get_iplug(&self) -> &PlugInterface {
+        &self.i_plug
+    }
+
+    fn get_iplug_mut(&mut self) -> &mut PlugInterface {
+        &mut self.i_plug
+    }
+
+    fn get_thumbnail(&self) -> Option<&Texture2D> {
+        self.thumbnail.as_ref()
+    }
+
+    fn set_thumbnail(&mut self, thumbnail: Texture2D) {
+        self.thumbnail = Some(thumbnail);
+    }
+
+    fn replace_plug_pos(
+        &mut self,
+        pos: Vector2,
+        new: Vector2,
+    ) -> Option<Vec<Vector2>> {
+        if let Some(i) = self.i_plug.inputs.remove(&pos.into()) {
+            if let Some(c) = self.i_plug.inputs.insert(new.into(), i) {
+                return Some(c);
+            }
+
+            return None;
+        }
+
+        if let Some(i) = self.i_plug.outputs.remove(&pos.into()) {
+            if let Some(c) = self.i_plug.outputs.insert(new.into(), i) {
+                return Some(c);
+            }
+
+            return None;
+        }
+
+        None
+    }
+
+    fn translate_wplugs(&mut self, offset: Vector2) {
+        let old_pos = self.get_pos();
+        self.translate(offset);
+        let new_pos = self.get_pos();
+        self.replace_plug_pos(old_pos + I_PLUG_OFFSET, new_pos + I_PLUG_OFFSET);
+        self.replace_plug_pos(old_pos + O_PLUG_OFFSET, new_pos + O_PLUG_OFFSET);
+    }
+
+    // joins the settlements given by the nodes upstream, there is no road
+    // without them
+    fn apply(&mut self, voxmap: &mut VoxelMap, globals: &mut Globals) {
+        let mut base = biome_grid(voxmap);
+
+        globals.roads =
+            build_roads(&mut base, &globals.settlements, &self.opts);
+
+        write_grid(voxmap, &base);
+    }
+}
+
+impl WidgetConfigurable for Roads {
+    fn configure(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        let panel_rect =
+            Rectangle::new(0.0, 0.0, 150.0, globals.dimensions.y + 1.0);
+
+        globals.workspace_locked = panel_rect
+            .check_collision_circle_rec(globals.s_mouse, SELECTION_OFFSET);
+
+        handle.gui_window_box(
+            panel_rect,
+            Some(&CString::new(&*self.id).expect("CString::new failed")),
+        );
+
+        let id = self.id.clone();
+        let row = |i: usize| {
+            Vector2::new(15.0, WINDOW_BOX_TITLE_SIZE + 10.0 + 37.0 * i as f32)
+        };
+
+        self.opts.city_distance = number_slider(
+            handle,
+            globals,
+            (&id, 0),
+            row(0),
+            "City max length",
+            self.opts.city_distance as f32,
+            (0.0, 500.0),
+        )
+        .round() as f64;
+
+        self.opts.village_distance = number_slider(
+            handle,
+            globals,
+            (&id, 1),
+            row(1),
+            "Village max length",
+            self.opts.village_distance as f32,
+            (0.0, 500.0),
+        )
+        .round() as f64;
+
+        self.opts.city_connections = number_slider(
+            handle,
+            globals,
+            (&id, 2),
+            row(2),
+            "City connections",
+            self.opts.city_connections as f32,
+            (0.0, 10.0),
+        )
+        .round() as usize;
+
+        self.opts.village_connections = number_slider(
+            handle,
+            globals,
+            (&id, 3),
+            row(3),
+            "Village connections",
+            self.opts.village_connections as f32,
+            (0.0, 10.0),
+        )
+        .round() as usize;
+
+        biome_toggles(
+            handle,
+            "Forbidden biomes",
+            row(4).y + 5.0,
+            &mut self.opts.forbidden,
+        );
+
+        if handle.gui_button(
+            Rectangle::new(5.0, globals.dimensions.y - 35.0, 140.0, 30.0),
+            Some(&CString::new("Reset").expect("CString::new failed")),
+        ) {
+            self.opts = RoadOpts::default();
+        }
+
+        WidgetSignal::None
+    }
+
+    fn get_opts(&self) -> WidgetOpts {
+        WidgetOpts::Roads(self.opts.clone())
+    }
+
+    fn set_opts(&mut self, opts: WidgetOpts) -> WidgetSignal {
+        if let WidgetOpts::Roads(opts) = opts {
+            self.opts = opts;
+        }
+
+        WidgetSignal::None
+    }
+}
+
+impl Widget for Roads {
+    fn render(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        self.draw_plug_links(handle);
+
+        handle.draw_rectangle_rec(self.rect, ROADS_BLOCK_COLOR);
+        handle.draw_rectangle_lines_ex(self.rect, 2, ROADS_BLOCK_BORDER_COLOR);
+
+        handle.draw_text(
+            &self.id,
+            self.rect.x as i32 + 2,
+            self.rect.y as i32 + 5,
+            15,
+            Color::WHITE,
+        );
+
+        self.draw_thumbnail(handle, THUMBNAIL_TOP);
+        self.draw_plugs(handle, globals);
+
+        WidgetSignal::None
+    }
+
+    fn call(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        self.render(handle, globals)
+    }
+
+    fn ready(&mut self) {
+        self.ready = true
+    }
+    fn unready(&mut self) {
+        self.ready = false
+    }
+    fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    fn get_type(&self) -> WidgetType {
+        WidgetType::Roads
+    }
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+    fn set_id(&mut self, id: String) {
+        self.id = id
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+    fn set_visible(&mut self, state: bool) {
+        self.visible = state
+    }
+    fn show(&mut self) {
+        self.visible = true
+    }
+    fn hide(&mut self) {
+        self.visible = false
+    }
+    fn toggle_visible(&mut self) {
+        self.visible ^= true
+    }
+
+    fn as_widget_rectangle(&self) -> Option<&dyn WidgetRectangle> {
+        Some(self as _)
+    }
+
+    fn as_widget_rectangle_mut(&mut self) -> Option<&mut dyn WidgetRectangle> {
+        Some(self as _)
+    }
+
+    fn as_widget_collidable(&self) -> Option<&dyn WidgetCollidable> {
+        Some(self as _)
+    }
+
+    fn as_widget_collidable_mut(
+        &mut self,
+    ) -> Option<&mut dyn WidgetCollidable> {
+        Some(self as _)
+    }
+
+    fn as_widget_plugable(&self) -> Option<&dyn WidgetPlugable> {
+        Some(self as _)
+    }
+
+    fn as_widget_plugable_mut(&mut self) -> Option<&mut dyn WidgetPlugable> {
+        Some(self as _)
+    }
+
+    fn as_widget_configurable(&self) -> Option<&dyn WidgetConfigurable> {
+        Some(self as _)
+    }
+
+    fn as_widget_configurable_mut(
+        &mut self,
+    ) -> Option<&mut dyn WidgetConfigurable> {
+        Some(self as _)
+    }
+}
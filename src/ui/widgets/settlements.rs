@@ -0,0 +1,428 @@
+use crate::display::settlements::*;
+use crate::ui::ui::WINDOW_BOX_TITLE_SIZE;
+use crate::widgets::number_box::*;
+use crate::widgets::*;
+
+use rand::prelude::*;
+use raylib::prelude::*;
+use std::ffi::CString;
+
+const SETTLEMENTS_BLOCK_COLOR: Color = Color::MAROON;
+const SETTLEMENTS_BLOCK_BORDER_COLOR: Color = Color::RED;
+const SETTLEMENTS_BLOCK_SIZE: f32 = 65.0;
+const TOGGLE_WIDTH: f32 = 68.0;
+const TOGGLE_HEIGHT: f32 = 18.0;
+const I_PLUG_OFFSET: Vector2 = Vector2 {
+    x: 0.0,
+    y: SETTLEMENTS_BLOCK_SIZE / 2.0,
+};
+const O_PLUG_OFFSET: Vector2 = Vector2 {
+    x: SETTLEMENTS_BLOCK_SIZE,
+    y: SETTLEMENTS_BLOCK_SIZE / 2.0,
+};
+
+pub struct Settlements {
+    pub opts: SettlementOpts,
+
+    pub i_plug: PlugInterface,
+    pub thumbnail: Option<Texture2D>,
+
+    pub rect: Rectangle,
+    pub id: String,
+    pub ready: bool,
+    pub visible: bool,
+}
+
+impl Settlements {
+    pub fn create(objmap: &ObjMap, globals: &mut Globals) -> Self {
+        let mut res = Settlements::default();
+        let mouse = globals.r_mouse;
+
+        res.rect = Rectangle::new(
+            mouse.x,
+            mouse.y,
+            SETTLEMENTS_BLOCK_SIZE,
+            SETTLEMENTS_BLOCK_SIZE,
+        );
+        res.id = format!("{}_{}", res.id, objmap.objs.unique_keys_count());
+
+        let input = mouse + I_PLUG_OFFSET;
+        let output = mouse + O_PLUG_OFFSET;
+
+        res.i_plug = PlugInterface::new(mouse);
+
+        res.i_plug.inputs.insert(input.into(), Vec::new());
+        res.i_plug.outputs.insert(output.into(), Vec::new());
+
+        res
+    }
+}
+
+impl Default for Settlements {
+    fn default() -> Self {
+        Self {
+            opts: SettlementOpts::default(),
+            i_plug: PlugInterface::default(),
+            thumbnail: None,
+
+            rect: Rectangle::EMPTY,
+            id: String::from("Settlements"),
+            ready: false,
+            visible: true,
+        }
+    }
+}
+
+impl WidgetRectangle for Settlements {
+    fn get_rect(&self) -> Rectangle {
+        self.rect
+    }
+
+    fn set_rect(&mut self, rect: Rectangle) {
+        self.rect = rect;
+    }
+}
+
+impl WidgetCollidable for Settlements {}
+
+impl WidgetPlugable for Settlements {
+    fn get_iplug(&self) -> &PlugInterface {
+        &self.i_plug
+    }
+
+    fn get_iplug_mut(&mut self) -> &mut PlugInterface {
+        &mut self.i_plug
+    }
+
+    fn get_thumbnail(&self) -> Option<&Texture2D> {
+        self.thumbnail.as_ref()
+    }
+
+    fn set_thumbnail(&mut self, thumbnail: Texture2D) {
+        self.thumbnail = Some(thumbnail);
+    }
+
+    fn replace_plug_pos(
+        &mut self,
+        pos: Vector2,
+        new: Vector2,
+    ) -> Option<Vec<Vector2>> {
+        if let Some(i) = self.i_plug.inputs.remove(&pos.into()) {
+            if let Some(c) = self.i_plug.inputs.insert(new.into(), i) {
+                return Some(c);
+            }
+
+            return None;
+        }
+
+        if let Some(i) = self.i_plug.outputs.remove(&pos.into()) {
+            if let Some(c) = self.i_plug.outputs.insert(new.into(), i) {
+                return Some(c);
+            }
+
+            return None;
+        }
+
+        None
+    }
+
+    fn translate_wplugs(&mut self, offset: Vector2) {
+        let old_pos = self.get_pos();
+        self.translate(offset);
+        let new_pos = self.get_pos();
+        self.replace_plug_pos(old_pos + I_PLUG_OFFSET, new_pos + I_PLUG_OFFSET);
+        self.replace_plug_pos(old_pos + O_PLUG_OFFSET, new_pos + O_PLUG_OFFSET);
+    }
+
+    // places the settlements on the biome map, the roads of the nodes
+    // upstream were built for other ones
+    fn apply(&mut self, voxmap: &mut VoxelMap, globals: &mut Globals) {
+        let mut base = biome_grid(voxmap);
+
+        globals.settlements = place_settlements(&mut base, &self.opts);
+        globals.roads.clear();
+
+        write_grid(voxmap, &base);
+    }
+}
+
+impl WidgetConfigurable for Settlements {
+    fn configure(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        let panel_rect =
+            Rectangle::new(0.0, 0.0, 150.0, globals.dimensions.y + 1.0);
+
+        globals.workspace_locked = panel_rect
+            .check_collision_circle_rec(globals.s_mouse, SELECTION_OFFSET);
+
+        handle.gui_window_box(
+            panel_rect,
+            Some(&CString::new(&*self.id).expect("CString::new failed")),
+        );
+
+        let id = self.id.clone();
+        let row = |i: usize| {
+            Vector2::new(15.0, WINDOW_BOX_TITLE_SIZE + 10.0 + 37.0 * i as f32)
+        };
+
+        self.opts.city_density = ((number_slider(
+            handle,
+            globals,
+            (&id, 0),
+            row(0),
+            "Cities / 10k cells",
+            self.opts.city_density as f32,
+            (0.0, 5.0),
+        ) * 100.0)
+            .round()
+            / 100.0) as f64;
+
+        self.opts.village_density = ((number_slider(
+            handle,
+            globals,
+            (&id, 1),
+            row(1),
+            "Villages / 10k cells",
+            self.opts.village_density as f32,
+            (0.0, 10.0),
+        ) * 100.0)
+            .round()
+            / 100.0) as f64;
+
+        self.opts.min_spacing = number_slider(
+            handle,
+            globals,
+            (&id, 2),
+            row(2),
+            "Min spacing",
+            self.opts.min_spacing as f32,
+            (0.0, 100.0),
+        )
+        .round() as f64;
+
+        let seed = row(3);
+
+        handle.gui_group_box(
+            Rectangle::new(seed.x, seed.y, 120.0, 20.0),
+            Some(&CString::new("Seed").expect("CString::new failed")),
+        );
+
+        if let Some(typed) = number_box(
+            handle,
+            globals,
+            (&id, 3),
+            Rectangle::new(seed.x, seed.y + 7.0, 120.0, 20.0),
+            self.opts.seed,
+        ) {
+            self.opts.seed = typed;
+        }
+
+        if handle.gui_button(
+            Rectangle::new(seed.x, seed.y + 32.0, 120.0, 20.0),
+            Some(&CString::new("Randomize").expect("CString::new failed")),
+        ) {
+            self.opts.seed = rand::thread_rng().gen();
+        }
+
+        biome_toggles(
+            handle,
+            "Allowed biomes",
+            seed.y + 65.0,
+            &mut self.opts.allowed,
+        );
+
+        if handle.gui_button(
+            Rectangle::new(5.0, globals.dimensions.y - 35.0, 140.0, 30.0),
+            Some(&CString::new("Reset").expect("CString::new failed")),
+        ) {
+            self.opts = SettlementOpts::default();
+        }
+
+        WidgetSignal::None
+    }
+
+    fn get_opts(&self) -> WidgetOpts {
+        WidgetOpts::Settlements(self.opts.clone())
+    }
+
+    fn set_opts(&mut self, opts: WidgetOpts) -> WidgetSignal {
+        if let WidgetOpts::Settlements(opts) = opts {
+            self.opts = opts;
+        }
+
+        WidgetSignal::None
+    }
+}
+
+// values of the voxel map as a grid, indexed by their coordinates
+pub fn biome_grid(voxmap: &VoxelMap) -> Vec<Vec<f64>> {
+    let mut res =
+        vec![vec![0.0; voxmap.dims.y as usize]; voxmap.dims.x as usize];
+
+    voxmap.voxels.iter().for_each(|x| {
+        res[x.coords.x as usize][x.coords.y as usize] = x.value;
+    });
+
+    res
+}
+
+pub fn write_grid(voxmap: &mut VoxelMap, grid: &[Vec<f64>]) {
+    voxmap.voxels.iter_mut().for_each(|x| {
+        x.value = grid[x.coords.x as usize][x.coords.y as usize];
+    });
+}
+
+// one toggle per biome in two columns, `codes` holds the selected ones
+pub fn biome_toggles(
+    handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+    title: &str,
+    top: f32,
+    codes: &mut Vec<f64>,
+) {
+    let rows = BIOMES.len().div_ceil(2);
+
+    handle.gui_group_box(
+        Rectangle::new(
+            5.0,
+            top,
+            TOGGLE_WIDTH * 2.0 + 4.0,
+            rows as f32 * (TOGGLE_HEIGHT + 2.0) + 12.0,
+        ),
+        Some(&CString::new(title).expect("CString::new failed")),
+    );
+
+    for (i, (name, code)) in BIOMES.iter().enumerate() {
+        let bounds = Rectangle::new(
+            7.0 + (i / rows) as f32 * TOGGLE_WIDTH,
+            top + 8.0 + (i % rows) as f32 * (TOGGLE_HEIGHT + 2.0),
+            TOGGLE_WIDTH,
+            TOGGLE_HEIGHT,
+        );
+        let active = codes.contains(code);
+        let toggled = handle.gui_toggle(
+            bounds,
+            Some(&CString::new(*name).expect("CString::new failed")),
+            active,
+        );
+
+        if toggled && !active {
+            codes.push(*code);
+        } else if !toggled && active {
+            codes.retain(|x| x != code);
+        }
+    }
+}
+
+impl Widget for Settlements {
+    fn render(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        self.draw_plug_links(handle);
+
+        handle.draw_rectangle_rec(self.rect, SETTLEMENTS_BLOCK_COLOR);
+        handle.draw_rectangle_lines_ex(
+            self.rect,
+            2,
+            SETTLEMENTS_BLOCK_BORDER_COLOR,
+        );
+
+        handle.draw_text(
+            &self.id,
+            self.rect.x as i32 + 2,
+            self.rect.y as i32 + 5,
+            15,
+            Color::WHITE,
+        );
+
+        self.draw_thumbnail(handle, THUMBNAIL_TOP);
+        self.draw_plugs(handle, globals);
+
+        WidgetSignal::None
+    }
+
+    fn call(
+        &mut self,
+        handle: &mut RaylibMode2D<'_, RaylibDrawHandle>,
+        globals: &mut Globals,
+    ) -> WidgetSignal {
+        self.render(handle, globals)
+    }
+
+    fn ready(&mut self) {
+        self.ready = true
+    }
+    fn unready(&mut self) {
+        self.ready = false
+    }
+    fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    fn get_type(&self) -> WidgetType {
+        WidgetType::Settlements
+    }
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+    fn set_id(&mut self, id: String) {
+        self.id = id
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+    fn set_visible(&mut self, state: bool) {
+        self.visible = state
+    }
+    fn show(&mut self) {
+        self.visible = true
+    }
+    fn hide(&mut self) {
+        self.visible = false
+    }
+    fn toggle_visible(&mut self) {
+        self.visible ^= true
+    }
+
+    fn as_widget_rectangle(&self) -> Option<&dyn WidgetRectangle> {
+        Some(self as _)
+    }
+
+    fn as_widget_rectangle_mut(&mut self) -> Option<&mut dyn WidgetRectangle> {
+        Some(self as _)
+    }
+
+    fn as_widget_collidable(&self) -> Option<&dyn WidgetCollidable> {
+        Some(self as _)
+    }
+
+    fn as_widget_collidable_mut(
+        &mut self,
+    ) -> Option<&mut dyn WidgetCollidable> {
+        Some(self as _)
+    }
+
+    fn as_widget_plugable(&self) -> Option<&dyn WidgetPlugable> {
+        Some(self as _)
+    }
+
+    fn as_widget_plugable_mut(&mut self) -> Option<&mut dyn WidgetPlugable> {
+        Some(self as _)
+    }
+
+    fn as_widget_configurable(&self) -> Option<&dyn WidgetConfigurable> {
+        Some(self as _)
+    }
+
+    fn as_widget_configurable_mut(
+        &mut self,
+    ) -> Option<&mut dyn WidgetConfigurable> {
+        Some(self as _)
+    }
+}
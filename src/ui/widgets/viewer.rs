@@ -0,0 +1,226 @@
+use crate::ui::ui::BG_COLOR;
+use raylib::prelude::*;
+
+use std::ffi::CString;
+
+// height of the relief, in pixels of the map, before exaggeration
+const VIEWER_HEIGHT: f32 = 32.0;
+const PANEL: Rectangle = Rectangle {
+    x: 10.0,
+    y: 10.0,
+    width: 230.0,
+    height: 80.0,
+};
+
+// Full window 3D preview of the altitude map, textured with the previewer
+// image (biomes and shading included).
+pub struct Viewer {
+    pub visible: bool,
+    pub camera: Camera3D,
+    // free camera instead of orbiting around the map
+    pub fly: bool,
+    pub exaggeration: f32,
+    // the model has to be built again
+    pub changed: bool,
+    // the camera mode has to be set again
+    pub camera_changed: bool,
+
+    model: Option<Model>,
+    water: Option<Model>,
+    // altitude of the water, in [0, 1] like the altitude map
+    sea_level: f32,
+    size: Vector2,
+}
+
+impl Viewer {
+    pub fn rebuild(
+        &mut self,
+        handle: &mut RaylibHandle,
+        rl_thread: &RaylibThread,
+        altmap: &Vec<Vec<f64>>,
+        colors: &Image,
+        sea_level: f64,
+    ) {
+        self.changed = false;
+        self.model = None;
+        self.water = None;
+        self.sea_level = sea_level as f32;
+
+        if altmap.is_empty() || altmap[0].is_empty() {
+            return;
+        }
+
+        let (w, h) = (altmap.len(), altmap[0].len());
+        let mut heights =
+            Image::gen_image_color(w as i32, h as i32, Color::BLACK);
+        for i in 0..w {
+            for j in 0..h {
+                let v = (altmap[i][j].clamp(0.0, 1.0) * 255.0) as u8;
+                heights.draw_pixel(i as i32, j as i32, rcolor(v, v, v, 255));
+            }
+        }
+
+        let size =
+            Vector3::new(w as f32, VIEWER_HEIGHT * self.exaggeration, h as f32);
+        let mesh = unsafe {
+            Mesh::gen_mesh_heightmap(rl_thread, &heights, size).make_weak()
+        };
+
+        let mut model = match handle.load_model_from_mesh(rl_thread, mesh) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("Couldn't build the 3D preview: {}", e);
+                return;
+            }
+        };
+
+        if let Ok(mut t) = handle.load_texture_from_image(rl_thread, colors) {
+            t.gen_texture_mipmaps();
+            let materials = model.materials_mut();
+            let maps = materials[0].maps_mut();
+            maps[MaterialMapIndex::MATERIAL_MAP_ALBEDO as usize].texture =
+                unsafe { t.unwrap() };
+        }
+
+        let water = unsafe {
+            Mesh::gen_mesh_plane(rl_thread, w as f32, h as f32, 1, 1)
+                .make_weak()
+        };
+
+        self.water = handle.load_model_from_mesh(rl_thread, water).ok();
+        self.model = Some(model);
+
+        if self.size != Vector2::new(w as f32, h as f32) {
+            self.size = Vector2::new(w as f32, h as f32);
+            self.reset_camera();
+        }
+    }
+
+    pub fn reset_camera(&mut self) {
+        let center = Vector3::new(self.size.x / 2.0, 0.0, self.size.y / 2.0);
+        self.camera = Camera3D::perspective(
+            center + Vector3::new(0.0, self.size.x / 2.0, self.size.y),
+            center,
+            Vector3::up(),
+            45.0,
+        );
+        self.camera_changed = true;
+    }
+
+    pub fn update_camera(&mut self, handle: &mut RaylibHandle) {
+        if self.camera_changed {
+            self.camera_changed = false;
+            let mode = if self.fly {
+                CameraMode::CAMERA_FREE
+            } else {
+                CameraMode::CAMERA_ORBITAL
+            };
+            handle.set_camera_mode(&self.camera, mode);
+        }
+
+        handle.update_camera(&mut self.camera);
+    }
+
+    pub fn draw(&mut self, handle: &mut RaylibDrawHandle) {
+        handle.clear_background(BG_COLOR);
+
+        {
+            let mut d3 = handle.begin_mode3D(&self.camera);
+
+            if let Some(model) = &self.model {
+                d3.draw_model(model, Vector3::zero(), 1.0, Color::WHITE);
+            }
+
+            if let Some(water) = &self.water {
+                let level = self.sea_level * VIEWER_HEIGHT * self.exaggeration;
+                let mut color = Color::BLUE;
+                color.a = 128;
+
+                d3.draw_model(
+                    water,
+                    Vector3::new(self.size.x / 2.0, level, self.size.y / 2.0),
+                    1.0,
+                    color,
+                );
+            }
+        }
+
+        if self.model.is_none() {
+            handle.draw_text(
+                "Nothing generated yet (F5)",
+                20,
+                100,
+                20,
+                Color::GRAY,
+            );
+        }
+
+        self.draw_panel(handle);
+    }
+
+    fn draw_panel(&mut self, handle: &mut RaylibDrawHandle) {
+        let (x, y) = (PANEL.x + 80.0, PANEL.y + 5.0);
+
+        handle.draw_rectangle_rec(PANEL, Color::RAYWHITE);
+
+        let exaggeration = handle.gui_slider_bar(
+            Rectangle::new(x, y, 120.0, 20.0),
+            Some(&CString::new("Exaggeration").expect("CString::new failed")),
+            Some(
+                &CString::new(format!("{:.1}", self.exaggeration))
+                    .expect("CString::new failed"),
+            ),
+            self.exaggeration,
+            0.5,
+            8.0,
+        );
+
+        if (exaggeration - self.exaggeration).abs() >= 0.1 {
+            self.exaggeration = (exaggeration * 10.0).round() / 10.0;
+            self.changed = true;
+        }
+
+        let fly = handle.gui_toggle(
+            Rectangle::new(x, y + 25.0, 120.0, 20.0),
+            Some(&CString::new("fly camera").expect("CString::new failed")),
+            self.fly,
+        );
+
+        if fly != self.fly {
+            self.fly = fly;
+            self.camera_changed = true;
+        }
+
+        if handle.gui_button(
+            Rectangle::new(x, y + 50.0, 120.0, 20.0),
+            Some(&CString::new("Reset camera").expect("CString::new failed")),
+        ) {
+            self.reset_camera();
+        }
+    }
+}
+
+impl Default for Viewer {
+    fn default() -> Self {
+        let mut res = Self {
+            visible: false,
+            camera: Camera3D::perspective(
+                Vector3::zero(),
+                Vector3::forward(),
+                Vector3::up(),
+                45.0,
+            ),
+            fly: false,
+            exaggeration: 1.0,
+            changed: true,
+            camera_changed: true,
+
+            model: None,
+            water: None,
+            sea_level: 0.0,
+            size: Vector2::new(512.0, 512.0),
+        };
+        res.reset_camera();
+        res
+    }
+}
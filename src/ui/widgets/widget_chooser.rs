@@ -1,14 +1,22 @@
 use crate::ui::ui::{SELECTION_OFFSET, WINDOW_MIN_HEIGHT};
+use crate::widgets::group::GroupOpts;
 use crate::widgets::*;
 use raylib::prelude::*;
 
 use std::ffi::CString;
 
+const WIDGET_NAMES: &str =
+    "None\nIO\nPerlin\nDsquare\nColoration\nMask\nAnalysis\nSettlements\nRoads";
+// index of the first group of the library in the list
+const LIBRARY_INDEX: i32 = 9;
+
 pub struct WidgetChooser {
     pub values: String,
     pub scroll_index: i32,
     pub selected_index: i32,
     pub callback: fn(&mut Self, &WidgetSignal),
+    // saved groups, listed after the widgets
+    pub library: Vec<GroupOpts>,
 
     pub rect: Rectangle,
     pub id: String,
@@ -30,6 +38,7 @@ impl WidgetChooser {
             scroll_index: 0,
             selected_index: -1,
             callback,
+            library: Vec::new(),
             ready: true,
             id,
         }
@@ -45,7 +54,7 @@ impl Default for WidgetChooser {
     fn default() -> Self {
         Self::new(
             Rectangle::new(0.0, 0.0, 150.0, WINDOW_MIN_HEIGHT),
-            String::from("None\nIO\nPerlin\nDsquare\nColoration"),
+            String::from(WIDGET_NAMES),
             |obj, res| {
                 if let WidgetSignal::Vec(v) = res {
                     if let [WidgetSignal::Bool(b), WidgetSignal::I32(i)] = v[..]
@@ -71,10 +80,28 @@ impl WidgetChooser {
             2 => WidgetType::Perlin,
             3 => WidgetType::Dsquare,
             4 => WidgetType::Coloration,
+            5 => WidgetType::Mask,
+            6 => WidgetType::Analysis,
+            7 => WidgetType::Settlements,
+            8 => WidgetType::Roads,
+            _ if self.get_group().is_some() => WidgetType::Group,
             _ => WidgetType::None,
         }
     }
 
+    pub fn get_group(&self) -> Option<&GroupOpts> {
+        let i = self.selected_index.checked_sub(LIBRARY_INDEX)?;
+        self.library.get(usize::try_from(i).ok()?)
+    }
+
+    pub fn set_library(&mut self, library: Vec<GroupOpts>) {
+        self.values = String::from(WIDGET_NAMES);
+        library.iter().for_each(|x| {
+            self.values.push_str(&format!("\nGroup: {}", x.name));
+        });
+        self.library = library;
+    }
+
     pub fn reset_sel_index(&mut self) {
         self.selected_index = 0;
     }
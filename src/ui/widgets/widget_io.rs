@@ -160,6 +160,21 @@ impl WidgetConfigurable for WidgetIO {
 
         res
     }
+
+    fn get_opts(&self) -> WidgetOpts {
+        WidgetOpts::Io(self.output_mode)
+    }
+
+    fn set_opts(&mut self, opts: WidgetOpts) -> WidgetSignal {
+        match opts {
+            WidgetOpts::Io(output_mode) if output_mode != self.output_mode => {
+                let res = self.io_swap_signal();
+                self.output_mode = output_mode;
+                res
+            }
+            _ => WidgetSignal::None,
+        }
+    }
 }
 
 impl Widget for WidgetIO {